@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use rltk::{BaseMap, Point};
+use specs::prelude::*;
+use super::headless::{Action, HeadlessGame};
+use super::{
+    Map, TileType, Monster, Position, EntryTrigger, Hidden, Spent, Viewshed, Item, ProvidesHealing, CombatStats,
+    RunState, RunStats, gui
+};
+
+/// Drink a potion once hp drops below this fraction of max hp.
+const LOW_HEALTH: f32 = 0.5;
+
+/// Turns spent on a level before giving up on exploring it and heading down.
+const TURNS_PER_LEVEL: i32 = 600;
+
+/// A simple player for soak testing. It fights whatever it can see,
+/// drinks potions when hurt, picks up any potion it has seen, explores
+/// until there is nothing left to find, then takes the stairs down.
+/// It steps around any traps it knows about where it can.
+#[derive(Default)]
+pub struct Bot {
+    depth: i32,
+    arrived_on_turn: i32,
+    /// The tiles left to walk, starting with the one the bot is on.
+    route: Vec<usize>,
+    /// The unexplored edge the bot is heading for.
+    exploring: Option<usize>,
+    /// The monster the bot is fighting.
+    hunting: Option<Entity>,
+    /// Tiles with traps the bot has seen, which routes avoid where they can.
+    known_traps: Vec<usize>,
+}
+
+impl Bot {
+    pub fn new() -> Bot {
+        Bot::default()
+    }
+
+    /// What the bot wants to do next, or None if it can't think of anything.
+    pub fn choose_action(&mut self, ecs: &World) -> Option<Action> {
+        let map = ecs.fetch::<Map>();
+        let turns = ecs.fetch::<RunStats>().turns;
+        if map.depth != self.depth {
+            self.depth = map.depth;
+            self.arrived_on_turn = turns;
+            self.exploring = None;
+            self.hunting = None;
+        }
+
+        let player_pos = *ecs.fetch::<Point>();
+        let player_entity = *ecs.fetch::<Entity>();
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        let visible = match viewsheds.get(player_entity) {
+            Some(viewshed) => &viewshed.visible_tiles,
+            None => return None,
+        };
+
+        if let Some(slot) = potion_to_drink(ecs, player_entity) {
+            return Some(Action::UseItem { slot });
+        }
+
+        // Monsters don't block the bot's paths, so walking
+        // at one attacks it once the bot catches up.
+        let mut path_map: Map = (*map).clone();
+        path_map.populate_blocked();
+
+        let positions = ecs.read_storage::<Position>();
+        let triggers = ecs.read_storage::<EntryTrigger>();
+        let hidden = ecs.read_storage::<Hidden>();
+        let spent = ecs.read_storage::<Spent>();
+        self.known_traps = (&positions, &triggers, !&hidden, !&spent).join()
+            .map(|(pos, _trigger, _hidden, _spent)| map.xy_idx(pos.x, pos.y))
+            .collect();
+
+        // Once the bot goes after a monster it keeps at it, even if a
+        // step takes the monster out of view for a moment.
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let nearest_monster = (&entities, &monsters, &positions).join()
+            .filter(|(_entity, _monster, pos)| visible.contains(&Point::new(pos.x, pos.y)))
+            .min_by(|a, b| {
+                let a = distance(player_pos, Point::new(a.2.x, a.2.y));
+                let b = distance(player_pos, Point::new(b.2.x, b.2.y));
+                a.total_cmp(&b)
+            })
+            .map(|(entity, _monster, _pos)| entity);
+        if nearest_monster.is_some() {
+            self.hunting = nearest_monster;
+        }
+        if let Some(monster) = self.hunting {
+            let target = positions.get(monster).map(|pos| Point::new(pos.x, pos.y));
+            match target.and_then(|target| self.step_towards(&path_map, player_pos, target)) {
+                Some(action) => return Some(action),
+                None => self.hunting = None,
+            }
+        }
+
+        let items = ecs.read_storage::<Item>();
+        let healing = ecs.read_storage::<ProvidesHealing>();
+        // Potions stay put, so any the bot has caught sight of count,
+        // not just those in view. Otherwise it can turn back and forth
+        // between a potion and wherever it was going as it drops from view.
+        let nearest_potion = (&items, &healing, &positions).join()
+            .map(|(_item, _healing, pos)| Point::new(pos.x, pos.y))
+            .filter(|pos| map.revealed_tiles[map.xy_idx(pos.x, pos.y)])
+            .min_by(|a, b| distance(player_pos, *a).total_cmp(&distance(player_pos, *b)));
+        if let Some(target) = nearest_potion {
+            if target == player_pos {
+                return Some(Action::PickUp);
+            }
+            if let Some(action) = self.step_towards(&path_map, player_pos, target) {
+                return Some(action);
+            }
+        }
+
+        let stairs = map.tiles.iter().enumerate()
+            .find(|(idx, tile)| **tile == TileType::DownStairs && map.revealed_tiles[*idx])
+            .map(|(idx, _tile)| Point::new(idx as i32 % map.width, idx as i32 / map.width));
+
+        // Keep exploring until the level has had its share of turns,
+        // or for as long as it takes to find the stairs.
+        if stairs.is_none() || turns - self.arrived_on_turn < TURNS_PER_LEVEL {
+            // Stick with one frontier until it is reached or seen past,
+            // rather than switching whenever another looks as close.
+            let start = map.xy_idx(player_pos.x, player_pos.y);
+            if self.exploring.is_some_and(|idx| idx == start || !borders_unknown(&map, idx)) {
+                self.exploring = None;
+            }
+            if self.exploring.is_none() {
+                self.exploring = nearest_unexplored(&path_map, player_pos);
+            }
+            if let Some(idx) = self.exploring {
+                let target = Point::new(idx as i32 % map.width, idx as i32 / map.width);
+                if let Some(action) = self.step_towards(&path_map, player_pos, target) {
+                    return Some(action);
+                }
+                self.exploring = None;
+            }
+        }
+
+        match stairs {
+            Some(target) if target == player_pos => Some(Action::Descend),
+            Some(target) => self.step_towards(&path_map, player_pos, target),
+            None => None,
+        }
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    rltk::DistanceAlg::Pythagoras.distance2d(a, b)
+}
+
+/// The inventory slot of a healing potion, if the player is hurt enough to need one.
+fn potion_to_drink(ecs: &World, player_entity: Entity) -> Option<usize> {
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let stats = combat_stats.get(player_entity)?;
+    if stats.hp as f32 >= stats.max_hp as f32 * LOW_HEALTH {
+        return None;
+    }
+
+    let healing = ecs.read_storage::<ProvidesHealing>();
+    gui::player_inventory(ecs).iter()
+        .position(|(item, _name)| healing.get(*item).is_some())
+}
+
+impl Bot {
+    /// The first step along an A* path from one point to another.
+    // The path is kept and followed until the target changes. Asking
+    // rltk for a fresh path every step can flip between two routes of
+    // about the same length, and leave the bot pacing back and forth.
+    fn step_towards(&mut self, map: &Map, from: Point, to: Point) -> Option<Action> {
+        let start = map.xy_idx(from.x, from.y);
+        let end = map.xy_idx(to.x, to.y);
+        if self.route.len() < 2 || self.route.last() != Some(&end) || self.route.first() != Some(&start) {
+            // Go around known traps, unless one is the only way through.
+            let mut careful_map = map.clone();
+            for idx in self.known_traps.iter().filter(|idx| **idx != start && **idx != end) {
+                careful_map.blocked[*idx] = true;
+            }
+            self.route = find_route(&careful_map, start, end)
+                .or_else(|| find_route(map, start, end))
+                .unwrap_or_default();
+            if self.route.len() < 2 {
+                self.route.clear();
+                return None;
+            }
+        }
+
+        self.route.remove(0);
+        let next = self.route[0] as i32;
+        Some(Action::Move {
+            dx: next % map.width - from.x,
+            dy: next / map.width - from.y,
+        })
+    }
+}
+
+/// The tiles from start to end, including both.
+fn find_route(map: &Map, start: usize, end: usize) -> Option<Vec<usize>> {
+    let path = rltk::a_star_search(start, end, map);
+    if path.success {
+        Some(path.steps)
+    } else {
+        // rltk's A* gives up on some long winding routes that do exist.
+        dijkstra_route(map, start, end)
+    }
+}
+
+/// A route found by walking downhill on a Dijkstra map of distances to the end.
+fn dijkstra_route(map: &Map, start: usize, end: usize) -> Option<Vec<usize>> {
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &[end], map, 1000.0);
+    // The end itself is never given a distance.
+    let distance = |idx: usize| if idx == end { 0.0 } else { dijkstra_map.map[idx] };
+
+    let mut route = vec![start];
+    let mut current = start;
+    while current != end {
+        let (next, next_distance) = map.get_available_exits(current).iter()
+            .map(|(idx, _cost)| (*idx, distance(*idx)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        if next_distance == f32::MAX || route.len() > map.tiles.len() {
+            return None;
+        }
+        route.push(next);
+        current = next;
+    }
+    Some(route)
+}
+
+/// The closest tile, other than the one the bot is on, that has been
+/// seen, can be walked on, and sits next to a tile that hasn't been seen yet.
+fn nearest_unexplored(map: &Map, from: Point) -> Option<usize> {
+    let start = map.xy_idx(from.x, from.y);
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &[start], map, 1000.0);
+
+    let mut best: Option<(f32, usize)> = None;
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        if idx == start || *tile == TileType::Wall || !map.revealed_tiles[idx] {
+            continue;
+        }
+        let distance = dijkstra_map.map[idx];
+        if distance == f32::MAX || best.is_some_and(|(best_distance, _)| best_distance <= distance) {
+            continue;
+        }
+        if borders_unknown(map, idx) {
+            best = Some((distance, idx));
+        }
+    }
+
+    best.map(|(_distance, idx)| idx)
+}
+
+/// Diagonals count too, since the map lets you squeeze between two walls that only touch at a corner.
+fn borders_unknown(map: &Map, idx: usize) -> bool {
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+    [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)].iter().any(|(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        nx >= 0 && nx < map.width && ny >= 0 && ny < map.height && !map.revealed_tiles[map.xy_idx(nx, ny)]
+    })
+}
+
+/// How a bot's game came to an end.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Died { cause: String },
+    /// The bot couldn't find anything to do.
+    Stuck,
+    /// The game was still going when the turn limit was reached.
+    OutOfTurns,
+    Panicked { message: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct GameReport {
+    pub seed: u64,
+    pub outcome: Outcome,
+    pub depth: i32,
+    pub turns: i32,
+    pub kills: i32,
+}
+
+/// Let the bot play one game from the given seed, without a window.
+/// A panic anywhere in the game is caught and reported as the outcome.
+pub fn play_game(seed: u64, max_turns: i32) -> GameReport {
+    let mut report = GameReport { seed, outcome: Outcome::Stuck, depth: 0, turns: 0, kills: 0 };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut game = HeadlessGame::new(seed);
+        let mut bot = Bot::new();
+
+        // Actions that don't use up a turn, such as picking up
+        // nothing, could otherwise keep the bot busy forever.
+        let mut actions_left = max_turns * 2;
+        let outcome = loop {
+            if game.runstate() == RunState::GameOver {
+                let cause = game.ecs().fetch::<RunStats>().cause_of_death.clone();
+                break Outcome::Died { cause: cause.unwrap_or_else(|| "Unknown".to_string()) };
+            }
+            if game.ecs().fetch::<RunStats>().turns >= max_turns || actions_left <= 0 {
+                break Outcome::OutOfTurns;
+            }
+            match bot.choose_action(game.ecs()) {
+                None => break Outcome::Stuck,
+                Some(action) => game.act(action),
+            }
+            actions_left -= 1;
+        };
+
+        let depth = game.ecs().fetch::<Map>().depth;
+        let stats = game.ecs().fetch::<RunStats>().clone();
+        (outcome, depth, stats.turns, stats.kills)
+    }));
+
+    match result {
+        Ok((outcome, depth, turns, kills)) => {
+            report.outcome = outcome;
+            report.depth = depth;
+            report.turns = turns;
+            report.kills = kills;
+        }
+        Err(payload) => {
+            let message = if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "Unknown panic".to_string()
+            };
+            report.outcome = Outcome::Panicked { message };
+        }
+    }
+    report
+}
+
+/// The results of many bot games.
+pub struct SoakReport {
+    pub games: Vec<GameReport>,
+}
+
+/// Play one game for each seed in turn.
+pub fn soak(seeds: impl Iterator<Item = u64>, max_turns: i32) -> SoakReport {
+    SoakReport { games: seeds.map(|seed| play_game(seed, max_turns)).collect() }
+}
+
+impl fmt::Display for SoakReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.games.len().max(1) as f32;
+        let average = |value: fn(&GameReport) -> i32| self.games.iter().map(value).sum::<i32>() as f32 / count;
+
+        writeln!(f, "Games played: {}", self.games.len())?;
+        writeln!(f, "Average depth: {:.2}", average(|g| g.depth))?;
+        writeln!(f, "Average turns: {:.1}", average(|g| g.turns))?;
+        writeln!(f, "Average kills: {:.2}", average(|g| g.kills))?;
+        writeln!(f, "Deepest level: {}", self.games.iter().map(|g| g.depth).max().unwrap_or(0))?;
+
+        // A BTreeMap keeps the causes in a stable order between runs.
+        let mut outcomes: BTreeMap<String, i32> = BTreeMap::new();
+        for game in self.games.iter() {
+            let key = match &game.outcome {
+                Outcome::Died { cause } => cause.clone(),
+                Outcome::Stuck => "Bot got stuck".to_string(),
+                Outcome::OutOfTurns => "Out of turns".to_string(),
+                Outcome::Panicked { .. } => "Panicked".to_string(),
+            };
+            *outcomes.entry(key).or_insert(0) += 1;
+        }
+        writeln!(f, "Outcomes:")?;
+        for (outcome, total) in outcomes.iter() {
+            writeln!(f, "  {:>6}  {}", total, outcome)?;
+        }
+
+        let panics: Vec<&GameReport> = self.games.iter()
+            .filter(|g| matches!(g.outcome, Outcome::Panicked { .. }))
+            .collect();
+        if !panics.is_empty() {
+            writeln!(f, "Panics:")?;
+            for game in panics {
+                if let Outcome::Panicked { message } = &game.outcome {
+                    writeln!(f, "  seed {}: {}", game.seed, message)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use rltk::Point;
+use specs::prelude::*;
+
+// Maps can be bigger than the screen, so only the part around the player
+// is drawn. Anything that turns a map position into a place on screen,
+// or the other way round for the mouse, goes through the camera.
+
+/// Columns of the map shown on screen.
+pub const VIEW_WIDTH: i32 = 80;
+
+/// Rows of the map shown on screen, above the log box.
+pub const VIEW_HEIGHT: i32 = 43;
+
+/// Which part of the map is on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// The map position drawn in the top left corner of the screen.
+    pub origin: Point,
+}
+
+impl Camera {
+    /// A camera with this map position in the middle of the view.
+    pub fn centred_on(centre: Point) -> Camera {
+        Camera { origin: Point::new(centre.x - VIEW_WIDTH / 2, centre.y - VIEW_HEIGHT / 2) }
+    }
+
+    /// The camera follows the player around.
+    pub fn new(ecs: &World) -> Camera {
+        Camera::centred_on(*ecs.fetch::<Point>())
+    }
+
+    /// Where a map position is drawn, or None if it is out of view.
+    pub fn world_to_screen(&self, pos: Point) -> Option<Point> {
+        let screen = Point::new(pos.x - self.origin.x, pos.y - self.origin.y);
+        if screen.x < 0 || screen.x >= VIEW_WIDTH || screen.y < 0 || screen.y >= VIEW_HEIGHT {
+            return None;
+        }
+        Some(screen)
+    }
+
+    /// The map position drawn at a point on screen, or None if the point is
+    /// outside the view. The position may still be off the edge of the map.
+    pub fn screen_to_world(&self, screen: Point) -> Option<Point> {
+        if screen.x < 0 || screen.x >= VIEW_WIDTH || screen.y < 0 || screen.y >= VIEW_HEIGHT {
+            return None;
+        }
+        Some(Point::new(screen.x + self.origin.x, screen.y + self.origin.y))
+    }
+}
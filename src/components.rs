@@ -1,13 +1,16 @@
-use specs::{prelude::*, rayon::string};
+use specs::prelude::*;
+use specs::saveload::{Marker, ConvertSaveload};
+use specs::error::NoError;
 use specs_derive::*;
-use rltk::RGB;
+use serde::{Serialize, Deserialize};
+use rltk::{DiceType, RGB};
 
 /// Non-NPC Player component
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Player {}
 
 /// Defines an entities location in space
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -15,15 +18,18 @@ pub struct Position {
 
 /// Entities can be rendered with a glyph
 /// foreground and background
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
     pub bg: RGB,
+    /// Where entities share a tile, the lowest render order is drawn on top.
+    /// The player is 0, monsters 1, and items and props 2.
+    pub render_order: i32,
 }
 
 /// Viewshed means "what can I see from here?"
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub dirty: bool,
@@ -31,67 +37,270 @@ pub struct Viewshed {
 }
 
 /// NPC Mob
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Monster {}
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Name {
     pub name: String
 }
 
 /// Indicates that this component can block other entities
 /// Used by the map_indexing_system
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct BlocksTile {}
 
+/// Stops anyone seeing through the tile, also used by the map_indexing_system
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct BlocksVisibility {}
+
+/// Shut doors block both movement and sight, open ones block neither.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Door {
+    pub open: bool,
+}
+
+/// Not drawn or named until the player has spotted it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Hidden {}
+
+/// Goes off when something steps onto its tile. What it does
+/// comes from its other components, such as InflictsDamage.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct EntryTrigger {}
+
+/// A trigger that only goes off once, and is harmless afterwards.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SingleActivation {}
+
+/// A single activation trigger that has already gone off.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Spent {}
+
+/// Sends whatever sets it off to somewhere random on the map.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Teleports {}
+
+/// The entity changed tiles this turn, so entry triggers should look at it.
+/// This is cleared every time the TriggerSystem runs, so it is never saved.
 #[derive(Component, Debug)]
+pub struct EntityMoved {}
+
+/// Hit points and natural attack. Everything else
+/// about how well an entity fights comes from its Attributes.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
-    pub defense: i32,
-    pub power: i32,
+    /// Damage dealt when fighting without a weapon.
+    pub damage: DiceType,
+}
+
+/// A single character attribute. The bonus follows from the base
+/// and modifiers, and is kept here so it isn't worked out every roll.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Attribute {
+    pub base: i32,
+    pub modifiers: i32,
+    pub bonus: i32,
+}
+
+impl Attribute {
+    pub fn new(base: i32) -> Attribute {
+        Attribute { base, modifiers: 0, bonus: crate::attr_bonus(base) }
+    }
+
+    pub fn raise_base(&mut self, amount: i32) {
+        self.base += amount;
+        self.bonus = crate::attr_bonus(self.value());
+    }
+
+    pub fn value(&self) -> i32 {
+        self.base + self.modifiers
+    }
+}
+
+/// The character model that combat stats are derived from.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Attributes {
+    /// Strength, adds to melee attack and damage rolls and to carry capacity.
+    pub might: Attribute,
+    /// Toughness, determines hit points.
+    pub fitness: Attribute,
+    /// Agility, makes an entity harder to hit.
+    pub quickness: Attribute,
+    pub intelligence: Attribute,
+}
+
+/// Earned by killing things, and what it adds up to.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
 }
 
 /// Indicates that a component can have
 /// melee intent.
-#[derive(Component, Debug)]
+#[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct WantsToMelee {
     pub target: Entity
 }
 
+/// Damage waiting to be applied, along with whoever dealt it.
+/// This is cleared every time the DamageSystem runs, so it is never saved.
 #[derive(Component, Debug)]
 pub struct SufferDamage {
-    pub amount: Vec<i32>
+    pub amount: Vec<(i32, Entity)>
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, source: Entity) {
         // If the entity already has a SufferDamage component
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, source));
         } else {
             // Add a new SufferDamage component
-            let dmg = SufferDamage { amount: vec![amount] };
+            let dmg = SufferDamage { amount: vec![(amount, source)] };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Item {}
 
-#[derive(Component, Debug)]
-pub struct Potion {
+/// Item is used up once it has been used.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Consumable {}
+
+/// Using this item restores hp to its targets.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesHealing {
     pub heal_amount: i32
 }
 
-#[derive(Component, Debug, Clone)]
+/// Item has to be aimed at a tile within range of the user.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ranged {
+    pub range: i32
+}
+
+/// Using this item deals damage to its targets. On a trap,
+/// the damage dealt to whatever sets it off.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsDamage {
+    pub damage: i32
+}
+
+/// Item hits everything within radius of the target tile,
+/// rather than just what stands on it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AreaOfEffect {
+    pub radius: i32
+}
+
+/// On an item, the number of turns it confuses its targets for.
+/// On a monster, the number of turns it has left to stumble around.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Confusion {
+    pub turns: i32
+}
+
+/// Doubles speed for this many turns. Like Confusion, this is both
+/// an item's effect and the status it leaves on whoever it hits.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Haste {
+    pub turns: i32
+}
+
+/// Halves speed for this many turns, the opposite of Haste.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Slow {
+    pub turns: i32
+}
+
+/// Counts down by the entity's speed every tick.
+/// Once it runs out, the entity gets to act.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Initiative {
+    pub current: i32
+}
+
+/// Given to whoever's initiative has just run out, for as long as it is their turn.
+/// This is cleared every time the InitiativeSystem ticks, so it is never saved.
+#[derive(Component, Debug)]
+pub struct MyTurn {}
+
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct InBackpack {
     pub owner: Entity
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToPickupItem {
     pub collected_by: Entity,
     pub item: Entity
-}
\ No newline at end of file
+}
+
+/// Intent to use an item that is in the backpack.
+/// Ranged items carry the tile they were aimed at.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<rltk::Point>
+}
+
+/// Intent to drop an item from the backpack
+/// back onto the map.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToDropItem {
+    pub item: Entity
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+    Armor,
+}
+
+/// Item can be worn or wielded in the given slot.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipmentSlot
+}
+
+/// Item is currently worn or wielded by its owner.
+/// Only one item can sit in a slot at a time.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot
+}
+
+/// Replaces the owner's own damage while equipped,
+/// and adds to their attack rolls.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct MeleeWeapon {
+    pub damage: DiceType,
+    pub to_hit: i32,
+}
+
+/// Added to the owner's armor class while equipped.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct DefenseBonus {
+    pub defense: i32
+}
+
+/// Marks the entities that get written to the save file.
+pub struct SerializeMe;
+
+/// Carries the resources that need saving through the
+/// save file, as only components can be serialized.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub map: super::map::Map,
+    pub log: super::gamelog::GameLog,
+    pub stats: super::gamelog::RunStats,
+    pub rng: rltk::RandomNumberGenerator,
+}
@@ -1,23 +1,104 @@
-use rltk::console;
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
-use super::{CombatStats, SufferDamage, Player};
+use super::{
+    CombatStats, Attributes, Experience, SufferDamage, Player, Name, RunState, RunStats, GameLog,
+    max_hit_points, xp_for_kill, xp_for_level
+};
 
-/// Handle generic damage sources.
+/// Handle generic damage sources, and reward whoever dealt the killing blow.
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStats>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, Name>,
         WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, Attributes>,
+        WriteStorage<'a, Experience>,
         WriteStorage<'a, SufferDamage>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (
+            entities,
+            player_entity,
+            mut run_stats,
+            mut log,
+            mut rng,
+            names,
+            mut combat_stats,
+            mut attributes,
+            mut experience,
+            mut damage
+        ) = data;
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+        // Who earned how much experience, handed out once all the damage is done.
+        let mut xp_earned: Vec<(Entity, i32)> = Vec::new();
+
+        for (victim, stats, damage) in (&entities, &mut combat_stats, &damage).join() {
+            for (amount, source) in damage.amount.iter() {
+                let was_alive = stats.hp > 0;
+                stats.hp -= amount;
+
+                // Only the blow that takes an entity below 1 hp counts as the kill.
+                if was_alive && stats.hp < 1 {
+                    if victim == *player_entity {
+                        run_stats.cause_of_death = Some(if *source == *player_entity {
+                            "Killed by their own hand".to_string()
+                        } else {
+                            let killer = names.get(*source).map_or("something", |n| n.name.as_str());
+                            format!("Slain by {}", killer)
+                        });
+                    } else if *source == *player_entity {
+                        run_stats.kills += 1;
+                    }
+
+                    if *source != victim {
+                        let victim_level = experience.get(victim).map_or(1, |e| e.level);
+                        xp_earned.push((*source, xp_for_kill(victim_level)));
+                    }
+                }
+            }
         }
         damage.clear();
+
+        for (killer, xp) in xp_earned {
+            let (Some(killer_experience), Some(killer_attributes), Some(killer_stats)) =
+                (experience.get_mut(killer), attributes.get_mut(killer), combat_stats.get_mut(killer))
+            else {
+                continue;
+            };
+            // Killing blows can land both ways in the same turn.
+            if killer_stats.hp < 1 {
+                continue;
+            }
+            killer_experience.xp += xp;
+
+            // A big enough kill can be worth more than one level.
+            while killer_experience.xp >= xp_for_level(killer_experience.level + 1) {
+                killer_experience.level += 1;
+
+                let raised = match rng.roll_dice(1, 4) {
+                    1 => &mut killer_attributes.might,
+                    2 => &mut killer_attributes.fitness,
+                    3 => &mut killer_attributes.quickness,
+                    _ => &mut killer_attributes.intelligence,
+                };
+                raised.raise_base(1);
+
+                killer_stats.max_hp = max_hit_points(killer_attributes, killer_experience.level);
+                killer_stats.hp = killer_stats.max_hp;
+
+                if killer == *player_entity {
+                    log.entries.push(format!("Congratulations, you are now level {}", killer_experience.level));
+                }
+            }
+        }
     }
 }
 
@@ -31,13 +112,14 @@ pub fn delete_the_dead(ecs: &mut World) {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let entities = ecs.entities();
-        
+
         for (entity, stats) in (&entities, &combat_stats).join() {
             if stats.hp < 1 {
                 let player = players.get(entity);
                 match player {
                     None => dead.push(entity),
-                    Some(_) => console::log("You are dead"),
+                    // The player stays in the world so the game over screen can report on them.
+                    Some(_) => *ecs.write_resource::<RunState>() = RunState::GameOver,
                 }
             }
         }
@@ -46,4 +128,4 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,34 @@
+use specs::prelude::*;
+use super::{Door, BlocksTile, BlocksVisibility, Renderable};
+
+// A door's state is spread over several components: whether it blocks
+// the tile, whether it blocks sight, and how it is drawn. Opening or
+// shutting one goes through here so they never disagree.
+
+/// Open or shut a door. The map indexing system picks up
+/// the change the next time it runs.
+pub fn set_door_open(
+    door_entity: Entity,
+    open: bool,
+    doors: &mut WriteStorage<Door>,
+    blockers: &mut WriteStorage<BlocksTile>,
+    sight_blockers: &mut WriteStorage<BlocksVisibility>,
+    renderables: &mut WriteStorage<Renderable>,
+) {
+    let Some(door) = doors.get_mut(door_entity) else {
+        return;
+    };
+    door.open = open;
+
+    if open {
+        blockers.remove(door_entity);
+        sight_blockers.remove(door_entity);
+    } else {
+        blockers.insert(door_entity, BlocksTile {}).expect("Unable to insert blocker");
+        sight_blockers.insert(door_entity, BlocksVisibility {}).expect("Unable to insert blocker");
+    }
+
+    if let Some(render) = renderables.get_mut(door_entity) {
+        render.glyph = rltk::to_cp437(if open { '/' } else { '+' });
+    }
+}
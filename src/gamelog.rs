@@ -1,3 +1,15 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct GameLog {
     pub entries: Vec<String>,
-}
\ No newline at end of file
+}
+/// A running tally of how the current game has gone,
+/// shown on the game over screen.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats {
+    pub seed: u64,
+    pub turns: i32,
+    pub kills: i32,
+    pub cause_of_death: Option<String>,
+}
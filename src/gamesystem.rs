@@ -0,0 +1,65 @@
+use super::Attributes;
+
+// The rules that turn attributes into numbers the rest of the game uses.
+// An attribute of 10 is average and gives no bonus, every two points
+// either side of that is worth +1 or -1.
+
+/// Armor class of an entity with no armor and no bonus from quickness.
+const BASE_ARMOR_CLASS: i32 = 10;
+
+/// Items anyone can carry, before their might is taken into account.
+const BASE_CARRY_CAPACITY: i32 = 10;
+
+/// Hit points gained with each level, before the fitness bonus.
+const HP_PER_LEVEL: i32 = 5;
+
+/// Experience for killing something of level 1.
+const XP_PER_VICTIM_LEVEL: i32 = 50;
+
+/// How much initiative an entity of average quickness loses each tick.
+const BASE_SPEED: i32 = 10;
+
+/// Initiative spent taking a turn.
+pub const ACTION_COST: i32 = 100;
+
+pub fn attr_bonus(value: i32) -> i32 {
+    (value - 10).div_euclid(2)
+}
+
+/// Two hit points for every point of fitness, plus the fitness bonus,
+/// and more again for every level past the first.
+pub fn max_hit_points(attributes: &Attributes, level: i32) -> i32 {
+    let per_level = i32::max(1, HP_PER_LEVEL + attributes.fitness.bonus);
+    i32::max(1, attributes.fitness.value() * 2 + attributes.fitness.bonus + (level - 1) * per_level)
+}
+
+/// Added to melee attack and damage rolls.
+pub fn melee_bonus(attributes: &Attributes) -> i32 {
+    attributes.might.bonus
+}
+
+/// What an attack roll has to reach to hit, before any armor is worn.
+pub fn armor_class(attributes: &Attributes) -> i32 {
+    BASE_ARMOR_CLASS + attributes.quickness.bonus
+}
+
+/// Initiative lost each tick, before haste or slow.
+pub fn speed(attributes: &Attributes) -> i32 {
+    i32::max(1, BASE_SPEED + attributes.quickness.bonus)
+}
+
+/// How many items fit in the backpack.
+pub fn carry_capacity(attributes: &Attributes) -> i32 {
+    i32::max(1, BASE_CARRY_CAPACITY + attributes.might.bonus * 2)
+}
+
+/// Experience for killing something of the given level.
+pub fn xp_for_kill(victim_level: i32) -> i32 {
+    victim_level * XP_PER_VICTIM_LEVEL
+}
+
+/// Total experience needed to reach a level: 100 for level 2,
+/// 300 for level 3, 600 for level 4 and so on.
+pub fn xp_for_level(level: i32) -> i32 {
+    (level - 1) * level / 2 * 100
+}
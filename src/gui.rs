@@ -1,10 +1,11 @@
-use rltk::{ Point, Rltk, RGB};
+use rltk::{ Point, Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
-use crate::{Map, Name, Position};
+use crate::{Map, Name, Position, InBackpack, Viewshed, Monster, Input, Replay, Camera, Hidden, VIEW_HEIGHT};
 
-use super::{CombatStats, Player, GameLog};
+use super::{CombatStats, Experience, Player, GameLog, RunStats};
 
-const GUI_HEIGHT: usize = 43;
+/// The log box sits just below the camera's view of the map.
+const GUI_HEIGHT: usize = VIEW_HEIGHT as usize;
 const GUI_WIDTH: usize = 79;
 
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
@@ -17,19 +18,32 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         RGB::named(rltk::BLACK)
     );
 
+    let map = ecs.fetch::<Map>();
+    let depth = format!("Depth: {}", map.depth);
+    ctx.print_color(2, GUI_HEIGHT, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &depth);
+
     let combat_stats = ecs.read_storage::<CombatStats>();
+    let experience = ecs.read_storage::<Experience>();
     let players = ecs.read_storage::<Player>();
     let game_log = ecs.fetch::<GameLog>();
 
-    let mut y = 44;
-    for s in game_log.entries.iter().rev() {
-        if y < 49 { 
-            ctx.print(2, y, s) 
+    // The newest entries go on the lines inside the panel's border.
+    for (y, s) in (GUI_HEIGHT as i32 + 1..).zip(game_log.entries.iter().rev()) {
+        if y < GUI_HEIGHT as i32 + 6 {
+            ctx.print(2, y, s)
         }
-        y += 1;
     }
 
-    for (_player, combat_stats) in (&players, &combat_stats).join() {
+    for (_player, combat_stats, experience) in (&players, &combat_stats, &experience).join() {
+        let level = format!("Level: {}", experience.level);
+        ctx.print_color(
+            GUI_WIDTH - level.len(),
+            GUI_HEIGHT,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &level
+        );
+
         let health = format!(" HP: {} / {}", combat_stats.hp, combat_stats.max_hp);
         ctx.print_color(
             12, 
@@ -41,7 +55,7 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         ctx.draw_bar_horizontal(
             28, 
             GUI_HEIGHT, 
-            51, 
+            38, 
             combat_stats.hp, 
             combat_stats.max_hp, 
             RGB::named(rltk::RED), 
@@ -63,16 +77,19 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
 
+    // The mouse is on screen, but what it points at is on the map.
     let mouse_pos = ctx.mouse_pos();
-    if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
-        return;
-    }
+    let mouse_world = match Camera::new(ecs).screen_to_world(Point::new(mouse_pos.0, mouse_pos.1)) {
+        Some(world) if map.in_bounds(world.x, world.y) => world,
+        _ => return,
+    };
 
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    for (name, position, _hidden) in (&names, &positions, !&hidden).join() {
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
+        if position.x == mouse_world.x && position.y == mouse_world.y && map.visible_tiles[idx] {
             tooltip.push(name.name.to_string());
         }
     }
@@ -89,9 +106,8 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
         if mouse_pos.0 > 40 {
             let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             let left_x = mouse_pos.0 - width;
-            let mut y = mouse_pos.1;
 
-            for str in tooltip.iter() {
+            for (y, str) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x, 
                     y, 
@@ -107,23 +123,21 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         y, 
                         RGB::named(rltk::WHITE), 
                         RGB::named(rltk::GREY), 
-                        &" ".to_string()
+                        " "
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x, 
                 arrow_pos.y, 
                 RGB::named(rltk::WHITE), 
                 RGB::named(rltk::GREY), 
-                &"<-".to_string()
+                "<-"
             );
         } else {
             let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
             let left_x = mouse_pos.0 + 3;
-            let mut y = mouse_pos.1;
-            for str in tooltip.iter() {
+            for (y, str) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x + 1, 
                     y,
@@ -138,18 +152,317 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         y, 
                         RGB::named(rltk::WHITE), 
                         RGB::named(rltk::GREY), 
-                        &" ".to_string()
+                        " "
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x, 
                 arrow_pos.y, 
                 RGB::named(rltk::WHITE), 
                 RGB::named(rltk::GREY), 
-                &"->".to_string()
+                "->"
             );
         }
     }
-}
\ No newline at end of file
+}
+
+/// The outcome of a menu for a single tick.
+#[derive(PartialEq, Copy, Clone)]
+pub enum ItemMenuResult {
+    Cancel,
+    NoResponse,
+    Selected,
+}
+
+/// Everything the player is carrying, in the order it is listed.
+pub fn player_inventory(ecs: &World) -> Vec<(Entity, String)> {
+    let player_entity = ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let entities = ecs.entities();
+
+    (&entities, &backpack, &names).join()
+        .filter(|(_entity, pack, _name)| pack.owner == *player_entity)
+        .map(|(entity, _pack, name)| (entity, name.name.clone()))
+        .collect()
+}
+
+/// Show everything the player is carrying so that they can pick an item to use.
+pub fn draw_inventory(ecs: &World, ctx: &mut Rltk) {
+    draw_item_menu(ecs, ctx, "Inventory");
+}
+
+/// Show everything the player is carrying so that they can pick an item to drop.
+pub fn draw_drop_item_menu(ecs: &World, ctx: &mut Rltk) {
+    draw_item_menu(ecs, ctx, "Drop Which Item?");
+}
+
+/// Draws a lettered list of the player's backpack.
+fn draw_item_menu(ecs: &World, ctx: &mut Rltk, title: &str) {
+    let inventory = player_inventory(ecs);
+    let count = inventory.len() as i32;
+
+    let top = 25 - (count / 2);
+    ctx.draw_box(15, top - 2, 31, count + 3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, top - 2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    ctx.print_color(18, top + count + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    for (j, (_entity, name)) in inventory.iter().enumerate() {
+        let y = top + j as i32;
+        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97 + j as rltk::FontCharType);
+        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+
+        ctx.print(21, y, name);
+    }
+}
+
+/// Maps the pressed letter back to the matching item in the backpack.
+pub fn item_menu_input(ecs: &World, input: &Input) -> (ItemMenuResult, Option<Entity>) {
+    match input.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                // letter_to_option maps a to 0, b to 1 and so on.
+                let inventory = player_inventory(ecs);
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < inventory.len() as i32 {
+                    return (ItemMenuResult::Selected, Some(inventory[selection as usize].0));
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        }
+    }
+}
+
+/// Where the targeting cursor starts: the closest visible monster
+/// within range, or the player themselves if there is none.
+pub fn default_target(ecs: &World, range: i32) -> Point {
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+
+    let mut best: Option<(f32, Point)> = None;
+    if let Some(visible) = viewsheds.get(*player_entity) {
+        for (_monster, pos) in (&monsters, &positions).join() {
+            let target = Point::new(pos.x, pos.y);
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, target);
+            if distance <= range as f32 && visible.visible_tiles.contains(&target) {
+                match best {
+                    Some((best_distance, _)) if best_distance <= distance => {}
+                    _ => best = Some((distance, target)),
+                }
+            }
+        }
+    }
+
+    match best {
+        None => *player_pos,
+        Some((_distance, target)) => target,
+    }
+}
+
+/// Every tile the player can see within range, or None if they can't see at all.
+fn target_cells(ecs: &World, range: i32) -> Option<Vec<Point>> {
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+
+    let visible = viewsheds.get(*player_entity)?;
+    Some(visible.visible_tiles.iter()
+        .filter(|idx| rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, **idx) <= range as f32)
+        .copied()
+        .collect())
+}
+
+/// Highlights every tile the player can target, the tile under the
+/// mouse, and the keyboard cursor.
+pub fn draw_ranged_target(ecs: &World, ctx: &mut Rltk, range: i32, cursor: Point) {
+    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select Target:");
+
+    let camera = Camera::new(ecs);
+    let available_cells = target_cells(ecs, range).unwrap_or_default();
+    for idx in available_cells.iter() {
+        if let Some(screen) = camera.world_to_screen(*idx) {
+            ctx.set_bg(screen.x, screen.y, RGB::named(rltk::BLUE));
+        }
+    }
+
+    let mouse_pos = ctx.mouse_pos();
+    let mouse_world = camera.screen_to_world(Point::new(mouse_pos.0, mouse_pos.1));
+    if mouse_world.is_some_and(|world| available_cells.contains(&world)) {
+        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
+    } else {
+        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
+    }
+    if let Some(screen) = camera.world_to_screen(cursor) {
+        ctx.set_bg(screen.x, screen.y, RGB::named(rltk::YELLOW));
+    }
+}
+
+/// Lets the player pick a tile to target, either by clicking it or by
+/// moving the cursor with the movement keys and pressing Enter.
+pub fn ranged_target_input(ecs: &World, input: &Input, range: i32, cursor: &mut Point) -> (ItemMenuResult, Option<Point>) {
+    let available_cells = match target_cells(ecs, range) {
+        None => return (ItemMenuResult::Cancel, None),
+        Some(cells) => cells,
+    };
+
+    if input.left_click {
+        let mouse_target = Camera::new(ecs).screen_to_world(Point::new(input.mouse_pos.0, input.mouse_pos.1));
+        return match mouse_target {
+            Some(target) if available_cells.contains(&target) => (ItemMenuResult::Selected, Some(target)),
+            _ => (ItemMenuResult::Cancel, None),
+        };
+    }
+
+    let (delta_x, delta_y) = match input.key {
+        None => return (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => return (ItemMenuResult::Cancel, None),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Space => {
+                if available_cells.contains(cursor) {
+                    return (ItemMenuResult::Selected, Some(*cursor));
+                }
+                return (ItemMenuResult::NoResponse, None);
+            }
+            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => (-1, 0),
+            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => (1, 0),
+            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => (0, -1),
+            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => (0, 1),
+            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => (1, -1),
+            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => (-1, -1),
+            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => (1, 1),
+            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => (-1, 1),
+            _ => return (ItemMenuResult::NoResponse, None),
+        }
+    };
+
+    // Only let the keyboard cursor wander over cells that can be targeted.
+    let moved = Point::new(cursor.x + delta_x, cursor.y + delta_y);
+    if available_cells.contains(&moved) {
+        *cursor = moved;
+    }
+    (ItemMenuResult::NoResponse, None)
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MainMenuSelection {
+    NewGame,
+    LoadGame,
+    Quit,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult {
+    NoSelection { selected: MainMenuSelection },
+    Selected { selected: MainMenuSelection },
+}
+
+/// Draws the title screen with the current option highlighted.
+/// Load Game is greyed out when there is no save to load.
+pub fn draw_main_menu(ctx: &mut Rltk, selection: MainMenuSelection, save_exists: bool) {
+    ctx.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Rusty Roguelike");
+
+    let options = [
+        (MainMenuSelection::NewGame, "Begin New Game", true),
+        (MainMenuSelection::LoadGame, "Load Game", save_exists),
+        (MainMenuSelection::Quit, "Quit", true),
+    ];
+    for (y, (option, text, enabled)) in (24..).zip(options.iter()) {
+        let colour = if *option == selection {
+            RGB::named(rltk::MAGENTA)
+        } else if !enabled {
+            RGB::named(rltk::GRAY)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color_centered(y, colour, RGB::named(rltk::BLACK), text);
+    }
+}
+
+/// Moves the highlight between the title screen's options.
+/// Load Game is still visited when there is no save, so that the keys
+/// pressed in a menu mean the same thing whether or not a save exists,
+/// which keeps replays in step.
+pub fn main_menu_input(input: &Input, selection: MainMenuSelection, save_exists: bool) -> MainMenuResult {
+    match input.key {
+        None => MainMenuResult::NoSelection { selected: selection },
+        Some(key) => match key {
+            VirtualKeyCode::Escape => MainMenuResult::NoSelection { selected: MainMenuSelection::Quit },
+            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
+                let selected = match selection {
+                    MainMenuSelection::NewGame => MainMenuSelection::Quit,
+                    MainMenuSelection::LoadGame => MainMenuSelection::NewGame,
+                    MainMenuSelection::Quit => MainMenuSelection::LoadGame,
+                };
+                MainMenuResult::NoSelection { selected }
+            }
+            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
+                let selected = match selection {
+                    MainMenuSelection::NewGame => MainMenuSelection::LoadGame,
+                    MainMenuSelection::LoadGame => MainMenuSelection::Quit,
+                    MainMenuSelection::Quit => MainMenuSelection::NewGame,
+                };
+                MainMenuResult::NoSelection { selected }
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                if selection == MainMenuSelection::LoadGame && !save_exists {
+                    return MainMenuResult::NoSelection { selected: selection };
+                }
+                MainMenuResult::Selected { selected: selection }
+            }
+            _ => MainMenuResult::NoSelection { selected: selection },
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult {
+    NoSelection,
+    QuitToMenu,
+}
+
+/// Tells the player how they died and how far they got.
+pub fn draw_game_over(ecs: &World, ctx: &mut Rltk) {
+    let stats = ecs.fetch::<RunStats>();
+    let map = ecs.fetch::<Map>();
+
+    let cause = stats.cause_of_death.clone().unwrap_or_else(|| "Died of mysterious causes".to_string());
+
+    ctx.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Your journey has ended!");
+    ctx.print_color_centered(17, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &cause);
+    ctx.print_color_centered(19, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You reached depth {}", map.depth));
+    ctx.print_color_centered(20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You survived for {} turns", stats.turns));
+    ctx.print_color_centered(21, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("You slew {} monsters", stats.kills));
+    ctx.print_color_centered(22, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), format!("Seed: {}", stats.seed));
+
+    ctx.print_color_centered(24, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to the menu.");
+}
+
+/// Any key press goes back to the main menu.
+pub fn game_over_input(input: &Input) -> GameOverResult {
+    match input.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::QuitToMenu,
+    }
+}
+
+/// Shows how far through a replay we are, and how to control it,
+/// along the bottom edge of the log box.
+pub fn draw_replay_status(ctx: &mut Rltk, replay: &Replay) {
+    let (played, total) = replay.progress();
+    let mode = if replay.paused {
+        "PAUSED"
+    } else if replay.fast_forward {
+        "FAST"
+    } else {
+        "PLAYING"
+    };
+    let status = format!(" REPLAY {} {}/{}  SPACE pause  . step  F fast ", mode, played, total);
+    ctx.print_color(2, GUI_HEIGHT + 6, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), &status);
+}
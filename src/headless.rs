@@ -0,0 +1,177 @@
+use rltk::{Point, VirtualKeyCode};
+use specs::prelude::*;
+use super::{State, RunState, Input, Replay, input::key_from_name, saveload_system};
+
+/// How many steps the game may take on its own before it is
+/// assumed to be stuck, rather than waiting on the player.
+const MAX_STEPS_WITHOUT_INPUT: i32 = 1000;
+
+/// Something the player does, for scripting a game without a keyboard.
+/// Each action is turned into the key presses that would do it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Step one tile, attacking whatever is in the way.
+    Move { dx: i32, dy: i32 },
+    PickUp,
+    Descend,
+    /// Use the item at this position in the inventory list.
+    UseItem { slot: usize },
+    /// Drop the item at this position in the inventory list.
+    DropItem { slot: usize },
+    Key(VirtualKeyCode),
+    Click { x: i32, y: i32 },
+}
+
+impl Action {
+    fn inputs(&self) -> Vec<Input> {
+        let key = |key| Input { key: Some(key), ..Default::default() };
+        match *self {
+            Action::Move { dx, dy } => vec![key(move_key(dx, dy))],
+            Action::PickUp => vec![key(VirtualKeyCode::G)],
+            Action::Descend => vec![key(VirtualKeyCode::Period)],
+            Action::UseItem { slot } => vec![key(VirtualKeyCode::I), key(slot_key(slot))],
+            Action::DropItem { slot } => vec![key(VirtualKeyCode::D), key(slot_key(slot))],
+            Action::Key(k) => vec![key(k)],
+            Action::Click { x, y } => vec![Input { key: None, mouse_pos: (x, y), left_click: true }],
+        }
+    }
+}
+
+fn move_key(dx: i32, dy: i32) -> VirtualKeyCode {
+    match (dx, dy) {
+        (-1, 0) => VirtualKeyCode::Left,
+        (1, 0) => VirtualKeyCode::Right,
+        (0, -1) => VirtualKeyCode::Up,
+        (0, 1) => VirtualKeyCode::Down,
+        (1, -1) => VirtualKeyCode::Numpad9,
+        (-1, -1) => VirtualKeyCode::Numpad7,
+        (1, 1) => VirtualKeyCode::Numpad3,
+        (-1, 1) => VirtualKeyCode::Numpad1,
+        _ => panic!("No key moves the player by ({}, {})", dx, dy),
+    }
+}
+
+/// Inventory menus are lettered, a for the first item and so on.
+fn slot_key(slot: usize) -> VirtualKeyCode {
+    let letter = (b'A' + slot as u8) as char;
+    key_from_name(&letter.to_string()).unwrap_or_else(|| panic!("No inventory letter for slot {}", slot))
+}
+
+/// Runs the game without a window, so it can be driven by tests and tools.
+/// The game goes through exactly the same RunState transitions as it
+/// does on screen, it just never draws anything.
+pub struct HeadlessGame {
+    pub state: State,
+}
+
+impl HeadlessGame {
+    /// Start a new game from the given seed, ready for the player's first move.
+    pub fn new(seed: u64) -> HeadlessGame {
+        HeadlessGame::with_state(State::new(None, Some(seed)))
+    }
+
+    /// As new, but every level is built by the named map builder.
+    pub fn with_map(seed: u64, map_builder_name: &str) -> HeadlessGame {
+        HeadlessGame::with_state(State::new(Some(map_builder_name.to_string()), Some(seed)))
+    }
+
+    /// As new, but every input the game reads is recorded to the given file.
+    pub fn recorded(seed: u64, path: &str) -> HeadlessGame {
+        let mut state = State::new(None, Some(seed));
+        state.recording_path = Some(path.to_string());
+        HeadlessGame::with_state(state)
+    }
+
+    /// Start the game a replay recorded, ready for play_replay.
+    pub fn replaying(replay: Replay) -> Result<HeadlessGame, String> {
+        let mut state = State::new(None, None);
+        state.start_replay(replay)?;
+        let mut game = HeadlessGame { state };
+        game.run_until_input();
+        Ok(game)
+    }
+
+    fn with_state(mut state: State) -> HeadlessGame {
+        state.new_game();
+        let mut game = HeadlessGame { state };
+        game.run_until_input();
+        game
+    }
+
+    pub fn ecs(&self) -> &World {
+        &self.state.ecs
+    }
+
+    pub fn ecs_mut(&mut self) -> &mut World {
+        &mut self.state.ecs
+    }
+
+    pub fn runstate(&self) -> RunState {
+        *self.state.ecs.fetch::<RunState>()
+    }
+
+    pub fn player_entity(&self) -> Entity {
+        *self.state.ecs.fetch::<Entity>()
+    }
+
+    pub fn player_position(&self) -> Point {
+        *self.state.ecs.fetch::<Point>()
+    }
+
+    /// Let the game run by itself until it is waiting on the player.
+    pub fn run_until_input(&mut self) {
+        for _ in 0..MAX_STEPS_WITHOUT_INPUT {
+            if self.runstate().reads_input() {
+                return;
+            }
+            self.state.step(&Input::default());
+        }
+        panic!("The game never stopped to wait for input");
+    }
+
+    /// Feed a single input to the game, then let it play out.
+    pub fn send(&mut self, input: Input) {
+        self.run_until_input();
+        self.state.play_input(&input);
+        self.run_until_input();
+    }
+
+    /// Play the rest of the replay through as fast as it goes.
+    pub fn play_replay(&mut self) {
+        let Some(replay) = self.state.replay.as_mut() else {
+            return;
+        };
+        replay.fast_forward = true;
+        while self.state.replay.as_ref().is_some_and(|replay| !replay.finished()) {
+            self.run_until_input();
+            self.state.step_replay();
+        }
+        self.run_until_input();
+    }
+
+    pub fn act(&mut self, action: Action) {
+        for input in action.inputs() {
+            self.send(input);
+        }
+    }
+
+    pub fn run_script(&mut self, actions: &[Action]) {
+        for action in actions {
+            self.act(*action);
+        }
+    }
+
+    /// Save the game in memory, in the same format as the save file.
+    pub fn save(&mut self) -> String {
+        saveload_system::save_to_string(&mut self.state.ecs)
+    }
+
+    /// Throw the world away and load a save made by save, the way
+    /// Load Game does, then let it run until the player's turn.
+    pub fn load(&mut self, data: &str) {
+        self.state.reset_world();
+        saveload_system::load_from_str(&mut self.state.ecs, data);
+        *self.state.ecs.write_resource::<RunState>() = RunState::PreRun;
+        self.run_until_input();
+    }
+}
@@ -0,0 +1,79 @@
+use specs::prelude::*;
+use super::{Initiative, MyTurn, Attributes, Haste, Slow, RunState, RunStats, ACTION_COST, speed};
+
+/// Decides who acts next. Every tick each entity's initiative counts down by
+/// its speed, and whoever runs out gets a turn. The player running out hands
+/// control back to the keyboard.
+pub struct InitiativeSystem {}
+
+impl<'a> System<'a> for InitiativeSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunState>,
+        WriteExpect<'a, RunStats>,
+        WriteStorage<'a, Initiative>,
+        WriteStorage<'a, MyTurn>,
+        ReadStorage<'a, Attributes>,
+        WriteStorage<'a, Haste>,
+        WriteStorage<'a, Slow>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut runstate,
+            mut run_stats,
+            mut initiatives,
+            mut turns,
+            attributes,
+            mut hasted,
+            mut slowed
+        ) = data;
+
+        if *runstate != RunState::Ticking {
+            return;
+        }
+
+        // Last tick's turns have been taken by now.
+        turns.clear();
+
+        for (entity, initiative) in (&entities, &mut initiatives).join() {
+            let mut speed = attributes.get(entity).map_or(1, speed);
+            if hasted.get(entity).is_some() {
+                speed *= 2;
+            }
+            if slowed.get(entity).is_some() {
+                speed = i32::max(1, speed / 2);
+            }
+
+            initiative.current -= speed;
+            if initiative.current > 0 {
+                continue;
+            }
+            initiative.current += ACTION_COST;
+            turns.insert(entity, MyTurn {}).expect("Unable to insert turn");
+
+            // Haste and slow wear off one turn at a time.
+            if let Some(haste) = hasted.get_mut(entity) {
+                haste.turns -= 1;
+                if haste.turns < 1 {
+                    hasted.remove(entity);
+                }
+            }
+            if let Some(slow) = slowed.get_mut(entity) {
+                slow.turns -= 1;
+                if slow.turns < 1 {
+                    slowed.remove(entity);
+                }
+            }
+
+            if entity == *player_entity {
+                run_stats.turns += 1;
+                *runstate = RunState::AwaitingInput;
+            }
+        }
+    }
+}
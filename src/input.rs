@@ -0,0 +1,59 @@
+use rltk::{Rltk, VirtualKeyCode};
+
+/// One tick's worth of player input, reduced to what the game acts on.
+/// Everything that reacts to the player reads one of these rather
+/// than the console, so recorded input can be played back through it.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Input {
+    pub key: Option<VirtualKeyCode>,
+    pub mouse_pos: (i32, i32),
+    pub left_click: bool,
+}
+
+impl Input {
+    /// Read this tick's input from the console.
+    /// Keys the game has no use for are dropped here.
+    pub fn from_ctx(ctx: &Rltk) -> Input {
+        Input {
+            key: ctx.key.filter(|key| key_name(*key).is_some()),
+            mouse_pos: ctx.mouse_pos(),
+            left_click: ctx.left_click,
+        }
+    }
+
+    /// Nothing was pressed or clicked.
+    pub fn is_empty(&self) -> bool {
+        self.key.is_none() && !self.left_click
+    }
+}
+
+// Every key the game responds to, along with the name it is written as
+// in replay files. VirtualKeyCode can't be serialized, hence the table.
+const KEYS: &[(VirtualKeyCode, &str)] = &[
+    (VirtualKeyCode::A, "A"), (VirtualKeyCode::B, "B"), (VirtualKeyCode::C, "C"),
+    (VirtualKeyCode::D, "D"), (VirtualKeyCode::E, "E"), (VirtualKeyCode::F, "F"),
+    (VirtualKeyCode::G, "G"), (VirtualKeyCode::H, "H"), (VirtualKeyCode::I, "I"),
+    (VirtualKeyCode::J, "J"), (VirtualKeyCode::K, "K"), (VirtualKeyCode::L, "L"),
+    (VirtualKeyCode::M, "M"), (VirtualKeyCode::N, "N"), (VirtualKeyCode::O, "O"),
+    (VirtualKeyCode::P, "P"), (VirtualKeyCode::Q, "Q"), (VirtualKeyCode::R, "R"),
+    (VirtualKeyCode::S, "S"), (VirtualKeyCode::T, "T"), (VirtualKeyCode::U, "U"),
+    (VirtualKeyCode::V, "V"), (VirtualKeyCode::W, "W"), (VirtualKeyCode::X, "X"),
+    (VirtualKeyCode::Y, "Y"), (VirtualKeyCode::Z, "Z"),
+    (VirtualKeyCode::Left, "Left"), (VirtualKeyCode::Right, "Right"),
+    (VirtualKeyCode::Up, "Up"), (VirtualKeyCode::Down, "Down"),
+    (VirtualKeyCode::Numpad1, "Numpad1"), (VirtualKeyCode::Numpad2, "Numpad2"),
+    (VirtualKeyCode::Numpad3, "Numpad3"), (VirtualKeyCode::Numpad4, "Numpad4"),
+    (VirtualKeyCode::Numpad6, "Numpad6"), (VirtualKeyCode::Numpad7, "Numpad7"),
+    (VirtualKeyCode::Numpad8, "Numpad8"), (VirtualKeyCode::Numpad9, "Numpad9"),
+    (VirtualKeyCode::NumpadEnter, "NumpadEnter"), (VirtualKeyCode::Return, "Return"),
+    (VirtualKeyCode::Space, "Space"), (VirtualKeyCode::Period, "Period"),
+    (VirtualKeyCode::Escape, "Escape"),
+];
+
+pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+    KEYS.iter().find(|(k, _)| *k == key).map(|(_, name)| *name)
+}
+
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEYS.iter().find(|(_, n)| *n == name).map(|(key, _)| *key)
+}
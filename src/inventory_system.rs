@@ -0,0 +1,302 @@
+use specs::prelude::*;
+use super::{
+    WantsToPickupItem, WantsToUseItem, WantsToDropItem, InBackpack, Name, Position,
+    Consumable, ProvidesHealing, InflictsDamage, AreaOfEffect, Confusion, Haste, Slow,
+    Equippable, Equipped, CombatStats, Attributes, SufferDamage, GameLog, Map, carry_capacity
+};
+
+/// Moves items that an entity wants to pick up off
+/// of the map and into that entity's backpack, if it has room.
+pub struct ItemCollectionSystem {}
+
+impl<'a> System<'a> for ItemCollectionSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, WantsToPickupItem>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Attributes>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack, attributes) = data;
+
+        for pickup in wants_pickup.join() {
+            if let Some(attributes) = attributes.get(pickup.collected_by) {
+                let carried = backpack.join().filter(|item| item.owner == pickup.collected_by).count() as i32;
+                if carried >= carry_capacity(attributes) {
+                    if pickup.collected_by == *player_entity {
+                        gamelog.entries.push("You can't carry any more.".to_string());
+                    }
+                    continue;
+                }
+            }
+
+            // An item in a backpack no longer has a place on the map.
+            positions.remove(pickup.item);
+            backpack.insert(pickup.item, InBackpack { owner: pickup.collected_by })
+                .expect("Unable to insert backpack entry");
+
+            if pickup.collected_by == *player_entity {
+                gamelog.entries.push(format!("You pick up the {}.", names.get(pickup.item).unwrap().name));
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+/// Applies the effects of items that entities want to use to
+/// whatever the item is aimed at, then uses up consumables.
+pub struct ItemUseSystem {}
+
+impl<'a> System<'a> for ItemUseSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Consumable>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, AreaOfEffect>,
+        WriteStorage<'a, Confusion>,
+        WriteStorage<'a, Haste>,
+        WriteStorage<'a, Slow>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Equippable>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            map,
+            entities,
+            mut wants_use,
+            names,
+            consumables,
+            healing,
+            inflict_damage,
+            aoe,
+            mut confused,
+            mut hasted,
+            mut slowed,
+            mut combat_stats,
+            mut suffer_damage,
+            equippable,
+            mut equipped,
+            mut backpack
+        ) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            let mut used_item = true;
+
+            // Work out who the item affects.
+            // No target means the user, otherwise whatever stands on
+            // the target tile (or around it for area of effect items).
+            let mut targets: Vec<Entity> = Vec::new();
+            match useitem.target {
+                None => targets.push(entity),
+                Some(target) => {
+                    match aoe.get(useitem.item) {
+                        None => {
+                            let idx = map.xy_idx(target.x, target.y);
+                            for mob in map.tile_content[idx].iter() {
+                                targets.push(*mob);
+                            }
+                        }
+                        Some(area_effect) => {
+                            let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
+                            blast_tiles.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                            for tile_idx in blast_tiles.iter() {
+                                let idx = map.xy_idx(tile_idx.x, tile_idx.y);
+                                for mob in map.tile_content[idx].iter() {
+                                    targets.push(*mob);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Equipping an item always applies to the user, and sends
+            // whatever already sits in that slot back to the backpack.
+            if let Some(can_equip) = equippable.get(useitem.item) {
+                let target_slot = can_equip.slot;
+
+                let mut to_unequip: Vec<Entity> = Vec::new();
+                for (item_entity, already_equipped, name) in (&entities, &equipped, &names).join() {
+                    if already_equipped.owner == entity && already_equipped.slot == target_slot {
+                        to_unequip.push(item_entity);
+                        if entity == *player_entity {
+                            gamelog.entries.push(format!("You unequip {}.", name.name));
+                        }
+                    }
+                }
+                for item in to_unequip.iter() {
+                    equipped.remove(*item);
+                    backpack.insert(*item, InBackpack { owner: entity }).expect("Unable to insert backpack entry");
+                }
+
+                equipped.insert(useitem.item, Equipped { owner: entity, slot: target_slot })
+                    .expect("Unable to insert equipped component");
+                backpack.remove(useitem.item);
+                if entity == *player_entity {
+                    gamelog.entries.push(format!("You equip {}.", names.get(useitem.item).unwrap().name));
+                }
+            }
+
+            if let Some(healer) = healing.get(useitem.item) {
+                for target in targets.iter() {
+                    if let Some(stats) = combat_stats.get_mut(*target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
+                        if entity == *player_entity {
+                            gamelog.entries.push(format!(
+                                "You use the {}, healing {} hp.",
+                                names.get(useitem.item).unwrap().name,
+                                healer.heal_amount
+                            ));
+                        }
+                    }
+                }
+            }
+
+            if let Some(damage) = inflict_damage.get(useitem.item) {
+                used_item = false;
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() {
+                        continue;
+                    }
+                    SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage, entity);
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).unwrap();
+                        let item_name = names.get(useitem.item).unwrap();
+                        gamelog.entries.push(format!(
+                            "You use {} on {}, inflicting {} hp.",
+                            item_name.name, mob_name.name, damage.damage
+                        ));
+                    }
+                    used_item = true;
+                }
+            }
+
+            // Confusion is both the item's effect and the status it applies,
+            // so gather the targets first to avoid borrowing the storage twice.
+            let mut add_confusion = Vec::new();
+            if let Some(confusion) = confused.get(useitem.item) {
+                used_item = false;
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() {
+                        continue;
+                    }
+                    add_confusion.push((*mob, confusion.turns));
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).unwrap();
+                        let item_name = names.get(useitem.item).unwrap();
+                        gamelog.entries.push(format!(
+                            "You use {} on {}, confusing them.",
+                            item_name.name, mob_name.name
+                        ));
+                    }
+                    used_item = true;
+                }
+            }
+            for (mob, turns) in add_confusion.iter() {
+                confused.insert(*mob, Confusion { turns: *turns }).expect("Unable to insert status");
+            }
+
+            // Haste and slow work the same way.
+            let mut add_haste = Vec::new();
+            if let Some(haste) = hasted.get(useitem.item) {
+                used_item = false;
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() {
+                        continue;
+                    }
+                    add_haste.push((*mob, haste.turns));
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).unwrap();
+                        gamelog.entries.push(format!("{} speeds up!", mob_name.name));
+                    }
+                    used_item = true;
+                }
+            }
+            for (mob, turns) in add_haste.iter() {
+                hasted.insert(*mob, Haste { turns: *turns }).expect("Unable to insert status");
+            }
+
+            let mut add_slow = Vec::new();
+            if let Some(slow) = slowed.get(useitem.item) {
+                used_item = false;
+                for mob in targets.iter() {
+                    if combat_stats.get(*mob).is_none() {
+                        continue;
+                    }
+                    add_slow.push((*mob, slow.turns));
+                    if entity == *player_entity {
+                        let mob_name = names.get(*mob).unwrap();
+                        gamelog.entries.push(format!("{} slows down.", mob_name.name));
+                    }
+                    used_item = true;
+                }
+            }
+            for (mob, turns) in add_slow.iter() {
+                slowed.insert(*mob, Slow { turns: *turns }).expect("Unable to insert status");
+            }
+
+            if used_item && consumables.get(useitem.item).is_some() {
+                entities.delete(useitem.item).expect("Delete failed");
+            }
+        }
+
+        wants_use.clear();
+    }
+}
+
+/// Takes items out of a backpack and places them
+/// back on the map where the dropper is standing.
+pub struct ItemDropSystem {}
+
+impl<'a> System<'a> for ItemDropSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToDropItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InBackpack>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack) = data;
+
+        for (entity, to_drop) in (&entities, &wants_drop).join() {
+            let mut dropper_pos: Position = Position { x: 0, y: 0 };
+            {
+                let dropped_pos = positions.get(entity).unwrap();
+                dropper_pos.x = dropped_pos.x;
+                dropper_pos.y = dropped_pos.y;
+            }
+            positions.insert(to_drop.item, dropper_pos).expect("Unable to insert position");
+            backpack.remove(to_drop.item);
+
+            if entity == *player_entity {
+                gamelog.entries.push(format!("You drop the {}.", names.get(to_drop.item).unwrap().name));
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
@@ -0,0 +1,588 @@
+use rltk::{GameState, Point, RandomNumberGenerator, Rltk};
+use specs::prelude::*;
+use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
+
+mod components;
+pub use components::*;
+mod gamesystem;
+pub use gamesystem::*;
+mod map; // Tell this file that the module 'map' is located at ./
+pub use map::*; // Import the map module for us in this file
+mod player;
+use player::*;
+mod rect;
+use rect::*;
+mod visibility_system;
+pub use visibility_system::VisibilitySystem;
+mod monster_ai_system;
+use monster_ai_system::*;
+mod initiative_system;
+use initiative_system::*;
+mod spawner;
+pub use spawner::*;
+pub mod raws;
+mod random_table;
+pub use random_table::*;
+mod map_indexing_system;
+pub use map_indexing_system::*;
+mod doors;
+pub use doors::*;
+mod trigger_system;
+pub use trigger_system::*;
+mod melee_combat_system;
+pub use melee_combat_system::*;
+mod damage_system;
+pub use damage_system::*;
+mod gui;
+pub use gui::*;
+pub mod camera;
+pub use camera::{Camera, VIEW_WIDTH, VIEW_HEIGHT};
+pub mod gamelog;
+pub use gamelog::{GameLog, RunStats};
+mod inventory_system;
+use inventory_system::*;
+pub mod map_builders;
+mod saveload_system;
+pub mod input;
+pub use input::Input;
+pub mod replay;
+pub use replay::{Recorder, Replay};
+pub mod headless;
+pub mod autoplay;
+
+pub struct State {
+    pub ecs: World,
+    /// Builder named on the command line, if any.
+    /// Otherwise every level picks one at random.
+    pub map_builder_name: Option<String>,
+    /// Seed named on the command line, if any.
+    /// Otherwise every new game picks one at random.
+    pub seed: Option<u64>,
+    /// Where new games are recorded to, if anywhere.
+    pub recording_path: Option<String>,
+    /// Records the inputs of the game being played, unless replaying one.
+    pub recorder: Option<Recorder>,
+    /// The recorded game being played back, if any.
+    pub replay: Option<Replay>,
+    /// Set when the player picks Quit, for whatever is running the game to act on.
+    pub quit_requested: bool,
+} // Braced struct declarations are not followed by a semi-colon.
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum RunState {
+    AwaitingInput,
+    PreRun,
+    /// Initiative counts down until it is the player's turn again,
+    /// with everyone else acting as their turns come up.
+    Ticking,
+    ShowInventory,
+    ShowDropItem,
+    ShowTargeting { range: i32, item: Entity, cursor: Point },
+    NextLevel,
+    SaveGame,
+    MainMenu { selection: gui::MainMenuSelection },
+    GameOver,
+}
+
+impl RunState {
+    /// States that wait on the player, rather than running by themselves.
+    /// Only these ever read input, so only their input gets recorded.
+    pub fn reads_input(&self) -> bool {
+        matches!(self,
+            RunState::AwaitingInput | RunState::ShowInventory | RunState::ShowDropItem |
+            RunState::ShowTargeting { .. } | RunState::MainMenu { .. } | RunState::GameOver
+        )
+    }
+}
+
+impl State {
+    /// A game sitting at the main menu, with nothing loaded yet.
+    pub fn new(map_builder_name: Option<String>, seed: Option<u64>) -> State {
+        let mut gs = State {
+            ecs: World::new(),
+            map_builder_name,
+            seed,
+            recording_path: None,
+            recorder: None,
+            replay: None,
+            quit_requested: false,
+        };
+        gs.reset_world();
+        gs
+    }
+
+    /// Skip the menu and play back a recorded game, built the same way it was recorded.
+    pub fn start_replay(&mut self, replay: Replay) -> Result<(), String> {
+        if let Some(name) = &replay.map_builder_name {
+            if map_builders::builder_by_name(name, 1).is_none() {
+                return Err(format!("Replay uses unknown map builder '{}'", name));
+            }
+        }
+        // Different spawns would send the game somewhere else entirely.
+        if replay.raws_fingerprint.is_some_and(|fingerprint| fingerprint != raws::load_raws().fingerprint) {
+            return Err(format!("Replay was recorded with different raws to {}", raws::RAWS_PATH));
+        }
+        self.map_builder_name = replay.map_builder_name.clone();
+        self.replay = Some(replay);
+        self.new_game();
+        Ok(())
+    }
+
+    fn run_systems(&mut self) {
+        let mut initiative = InitiativeSystem{};
+        initiative.run_now(&self.ecs);
+
+        // Doors may have opened or shut since the map was last indexed,
+        // and what everyone can see depends on them.
+        let mut map_index = MapIndexingSystem{};
+        map_index.run_now(&self.ecs);
+
+        let mut vis = VisibilitySystem{};
+        vis.run_now(&self.ecs);
+
+        let mut mob = MonsterAI{};
+        mob.run_now(&self.ecs);
+
+        map_index.run_now(&self.ecs);
+
+        let mut triggers = TriggerSystem{};
+        triggers.run_now(&self.ecs);
+
+        let mut melee_combat = MeleeCombatSystem{};
+        melee_combat.run_now(&self.ecs);
+
+        let mut pickup = ItemCollectionSystem{};
+        pickup.run_now(&self.ecs);
+
+        // Items queue damage too, so they go before it is dealt out.
+        let mut items = ItemUseSystem{};
+        items.run_now(&self.ecs);
+
+        let mut damage = DamageSystem{};
+        damage.run_now(&self.ecs);
+
+        let mut drop_items = ItemDropSystem{};
+        drop_items.run_now(&self.ecs);
+        
+        self.ecs.maintain(); // Apply changes to the world now.
+    }
+
+    /// Build a new map for the given depth, populate it,
+    /// and drop the player at its starting position.
+    fn generate_world_map(&mut self, new_depth: i32) {
+        let mut builder = {
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let mut builder = match &self.map_builder_name {
+                None => map_builders::random_builder(new_depth, &mut rng),
+                Some(name) => map_builders::builder_by_name(name, new_depth)
+                    .expect("Map builder name was checked at startup"),
+            };
+            builder.build_map(&mut rng);
+            builder
+        };
+        builder.spawn_entities(&mut self.ecs);
+        let Position { x: player_x, y: player_y } = builder.get_starting_position();
+        {
+            let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            *worldmap_resource = builder.get_map();
+        }
+
+        // Move the player, and the point that follows them, to the start.
+        let mut player_position = self.ecs.write_resource::<Point>();
+        *player_position = Point::new(player_x, player_y);
+        let mut position_components = self.ecs.write_storage::<Position>();
+        let player_entity = self.ecs.fetch::<Entity>();
+        if let Some(player_pos_comp) = position_components.get_mut(*player_entity) {
+            player_pos_comp.x = player_x;
+            player_pos_comp.y = player_y;
+        }
+
+        // The player has never seen this map, so make them look around.
+        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
+        if let Some(vs) = viewshed_components.get_mut(*player_entity) {
+            vs.dirty = true;
+        }
+    }
+
+    /// Everything except the player and what they are carrying
+    /// gets left behind when they change level.
+    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
+        let entities = self.ecs.entities();
+        let player = self.ecs.read_storage::<Player>();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let player_entity = self.ecs.fetch::<Entity>();
+
+        let mut to_delete: Vec<Entity> = Vec::new();
+        for entity in entities.join() {
+            let is_player = player.get(entity).is_some();
+            let carried_by_player = backpack.get(entity)
+                .is_some_and(|pack| pack.owner == *player_entity);
+            let worn_by_player = equipped.get(entity)
+                .is_some_and(|eq| eq.owner == *player_entity);
+
+            if !is_player && !carried_by_player && !worn_by_player {
+                to_delete.push(entity);
+            }
+        }
+
+        to_delete
+    }
+
+    /// Throw away the current world and replace it with an empty one.
+    /// Nothing from the previous game survives this, not even the
+    /// marker allocator or the random number generator.
+    fn reset_world(&mut self) {
+        self.ecs = World::new();
+
+        // Register components.
+        self.ecs.register::<Position>();
+        self.ecs.register::<Renderable>();
+        self.ecs.register::<Player>();
+        self.ecs.register::<Viewshed>();
+        self.ecs.register::<Monster>();
+        self.ecs.register::<Name>();
+        self.ecs.register::<BlocksTile>();
+        self.ecs.register::<BlocksVisibility>();
+        self.ecs.register::<Door>();
+        self.ecs.register::<Hidden>();
+        self.ecs.register::<EntryTrigger>();
+        self.ecs.register::<SingleActivation>();
+        self.ecs.register::<Spent>();
+        self.ecs.register::<Teleports>();
+        self.ecs.register::<EntityMoved>();
+        self.ecs.register::<CombatStats>();
+        self.ecs.register::<Attributes>();
+        self.ecs.register::<Experience>();
+        self.ecs.register::<WantsToMelee>();
+        self.ecs.register::<SufferDamage>();
+        self.ecs.register::<Item>();
+        self.ecs.register::<Consumable>();
+        self.ecs.register::<ProvidesHealing>();
+        self.ecs.register::<Ranged>();
+        self.ecs.register::<InflictsDamage>();
+        self.ecs.register::<AreaOfEffect>();
+        self.ecs.register::<Confusion>();
+        self.ecs.register::<Haste>();
+        self.ecs.register::<Slow>();
+        self.ecs.register::<Initiative>();
+        self.ecs.register::<MyTurn>();
+        self.ecs.register::<Equippable>();
+        self.ecs.register::<Equipped>();
+        self.ecs.register::<MeleeWeapon>();
+        self.ecs.register::<DefenseBonus>();
+        self.ecs.register::<InBackpack>();
+        self.ecs.register::<WantsToPickupItem>();
+        self.ecs.register::<WantsToUseItem>();
+        self.ecs.register::<WantsToDropItem>();
+        self.ecs.register::<SimpleMarker<SerializeMe>>();
+        self.ecs.register::<SerializationHelper>();
+
+        self.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+
+        // Every roll in the game comes from this one generator, so the
+        // same seed and the same inputs always play out the same way.
+        let seed = match &self.replay {
+            Some(replay) => replay.seed,
+            None => self.seed.unwrap_or_else(|| RandomNumberGenerator::new().next_u64()),
+        };
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+
+        // Read fresh for every game, so edits to the raws show up without a restart.
+        self.ecs.insert(raws::load_raws());
+
+        // The map is filled in, and the player moved onto it, by generate_world_map.
+        self.ecs.insert(Map::new(1, 1, 1));
+        let player_entity = spawner::player(&mut self.ecs, 0, 0);
+
+        // Insert a point that follows the player around.
+        // This is used to enable interaction with monsters.
+        self.ecs.insert(Point::new(0, 0));
+        self.ecs.insert(player_entity);
+        self.ecs.insert(RunState::MainMenu { selection: gui::MainMenuSelection::NewGame });
+        self.ecs.insert(GameLog { entries: vec!["Welcome to Rusty Roguelike".to_string()] });
+        self.ecs.insert(RunStats { seed, ..Default::default() });
+    }
+
+    /// Act on input from the player, recording it if the game reads it.
+    pub fn play_input(&mut self, input: &Input) {
+        let runstate = *self.ecs.fetch::<RunState>();
+        if let Some(recorder) = &mut self.recorder {
+            if runstate.reads_input() && !input.is_empty() {
+                recorder.record(input);
+            }
+        }
+        self.step(input);
+    }
+
+    /// Advance the replay by one step, feeding in the next
+    /// recorded input if the game is waiting on one.
+    pub fn step_replay(&mut self) {
+        let runstate = *self.ecs.fetch::<RunState>();
+        let mut input = Input::default();
+        if runstate.reads_input() {
+            if let Some(recorded) = self.replay.as_mut().and_then(|replay| replay.next_input()) {
+                input = recorded;
+            }
+        }
+        self.step(&input);
+    }
+
+    /// Start a new game, and a new recording of it.
+    pub fn new_game(&mut self) {
+        self.reset_world();
+        self.generate_world_map(1);
+        *self.ecs.write_resource::<RunState>() = RunState::PreRun;
+
+        self.recorder = None;
+        if let (None, Some(path)) = (&self.replay, &self.recording_path) {
+            let seed = self.ecs.fetch::<RunStats>().seed;
+            let raws_fingerprint = self.ecs.fetch::<raws::Raws>().fingerprint;
+            self.recorder = Recorder::start(path, seed, raws_fingerprint, self.map_builder_name.as_deref())
+                .map_err(|e| rltk::console::log(format!("Unable to record this game: {}", e)))
+                .ok();
+        }
+    }
+
+    /// Replays keep their saves in memory, so they never touch the real save.
+    fn save_game(&mut self) {
+        match &mut self.replay {
+            Some(replay) => replay.save = Some(saveload_system::save_to_string(&mut self.ecs)),
+            None => {
+                saveload_system::save_game(&mut self.ecs);
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.saved = true;
+                }
+            }
+        }
+    }
+
+    fn save_exists(&self) -> bool {
+        match &self.replay {
+            Some(replay) => replay.save.is_some(),
+            None => saveload_system::does_save_exist(),
+        }
+    }
+
+    /// The save is deleted once loaded, so each game can only be resumed once.
+    fn load_game(&mut self) {
+        self.reset_world();
+        *self.ecs.write_resource::<RunState>() = RunState::PreRun;
+        match &mut self.replay {
+            Some(replay) => {
+                let data = replay.save.take().expect("Replay loaded a game it never saved");
+                saveload_system::load_from_str(&mut self.ecs, &data);
+            }
+            None => {
+                saveload_system::load_game(&mut self.ecs);
+                saveload_system::delete_save();
+
+                // A save from before this recording started can't be replayed.
+                match &mut self.recorder {
+                    Some(recorder) if recorder.saved => recorder.saved = false,
+                    _ => self.recorder = None,
+                }
+            }
+        }
+    }
+
+    fn goto_next_level(&mut self) {
+        let to_delete = self.entities_to_remove_on_level_change();
+        for target in to_delete {
+            self.ecs.delete_entity(target).expect("Unable to delete entity");
+        }
+
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.generate_world_map(current_depth + 1);
+
+        let mut gamelog = self.ecs.fetch_mut::<GameLog>();
+        gamelog.entries.push("You descend to the next level.".to_string());
+    }
+}
+
+impl GameState for State {
+    // For the Struct State, implement the Tick function from
+    // the trait GameState.
+    fn tick(&mut self, ctx: &mut Rltk) {
+        let live_input = Input::from_ctx(ctx);
+
+        if self.replay.is_none() {
+            self.play_input(&live_input);
+        } else {
+            // While replaying, the keyboard drives the playback controls
+            // and the game only sees recorded input.
+            let steps = {
+                let replay = self.replay.as_mut().unwrap();
+                replay.handle_controls(&live_input);
+                replay.steps_per_frame()
+            };
+            for _ in 0..steps {
+                self.step_replay();
+            }
+        }
+
+        if self.quit_requested {
+            ctx.quit();
+        }
+        self.render(ctx);
+
+        if let Some(replay) = &self.replay {
+            gui::draw_replay_status(ctx, replay);
+            if replay.finished() {
+                self.replay = None;
+                self.ecs.fetch_mut::<GameLog>().entries.push("The replay has ended, you have control.".to_string());
+            }
+        }
+    }
+}
+
+impl State {
+    /// Draw the world and whatever menu is open over it.
+    fn render(&self, ctx: &mut Rltk) {
+        ctx.cls(); // Clear the active terminal.
+
+        let runstate = *self.ecs.fetch::<RunState>();
+
+        // Draw the world first so that any menu is drawn on top of it.
+        // The main menu and game over screens take up the whole terminal.
+        let in_game = !matches!(runstate, RunState::MainMenu { .. } | RunState::GameOver);
+        if in_game {
+            draw_map(&self.ecs, ctx);
+
+            let camera = Camera::new(&self.ecs);
+            for (pos, render) in entities_to_draw(&self.ecs) {
+                if let Some(screen) = camera.world_to_screen(pos) {
+                    ctx.set(screen.x, screen.y, render.fg, render.bg, render.glyph);
+                }
+            }
+
+            gui::draw_ui(&self.ecs, ctx);
+        }
+
+        match runstate {
+            RunState::ShowInventory => gui::draw_inventory(&self.ecs, ctx),
+            RunState::ShowDropItem => gui::draw_drop_item_menu(&self.ecs, ctx),
+            RunState::ShowTargeting { range, cursor, .. } => gui::draw_ranged_target(&self.ecs, ctx, range, cursor),
+            RunState::MainMenu { selection } => gui::draw_main_menu(ctx, selection, self.save_exists()),
+            RunState::GameOver => gui::draw_game_over(&self.ecs, ctx),
+            _ => {}
+        }
+    }
+
+    /// Advance the game by one step of its state machine, acting on the given input.
+    /// This never touches the console, so the game can be run without a window.
+    pub fn step(&mut self, input: &Input) {
+        let mut new_runstate;
+        {
+            let runstate = self.ecs.fetch::<RunState>();
+            new_runstate = *runstate;
+        }
+
+        match new_runstate {
+            RunState::PreRun => {
+                self.run_systems();
+                new_runstate = RunState::AwaitingInput;
+            }
+            RunState::AwaitingInput => {
+                new_runstate = player_input(self, input);
+            }
+            RunState::Ticking => {
+                // The initiative system stops the clock when the player's turn comes up.
+                // The dead are cleared away every tick, so they don't hold their
+                // ground until then, and the player dying ends the game straight away.
+                while new_runstate == RunState::Ticking {
+                    self.run_systems();
+                    damage_system::delete_the_dead(&mut self.ecs);
+                    new_runstate = *self.ecs.fetch::<RunState>();
+                }
+            }
+            RunState::NextLevel => {
+                self.goto_next_level();
+                new_runstate = RunState::PreRun;
+            }
+            RunState::SaveGame => {
+                self.save_game();
+                new_runstate = RunState::MainMenu { selection: gui::MainMenuSelection::LoadGame };
+            }
+            RunState::MainMenu { selection } => {
+                match gui::main_menu_input(input, selection, self.save_exists()) {
+                    gui::MainMenuResult::NoSelection { selected } => {
+                        new_runstate = RunState::MainMenu { selection: selected };
+                    }
+                    gui::MainMenuResult::Selected { selected } => match selected {
+                        gui::MainMenuSelection::NewGame => {
+                            self.new_game();
+                            new_runstate = RunState::PreRun;
+                        }
+                        gui::MainMenuSelection::LoadGame => {
+                            self.load_game();
+                            new_runstate = RunState::PreRun;
+                        }
+                        gui::MainMenuSelection::Quit => self.quit_requested = true,
+                    },
+                }
+            }
+            RunState::GameOver => {
+                if gui::game_over_input(input) == gui::GameOverResult::QuitToMenu {
+                    new_runstate = RunState::MainMenu { selection: gui::MainMenuSelection::NewGame };
+                }
+            }
+            RunState::ShowInventory => {
+                let (result, item) = gui::item_menu_input(&self.ecs, input);
+                match result {
+                    ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    ItemMenuResult::NoResponse => {}
+                    ItemMenuResult::Selected => {
+                        let item_entity = item.unwrap();
+                        let is_ranged = self.ecs.read_storage::<Ranged>();
+                        if let Some(ranged) = is_ranged.get(item_entity) {
+                            new_runstate = RunState::ShowTargeting {
+                                range: ranged.range,
+                                item: item_entity,
+                                cursor: gui::default_target(&self.ecs, ranged.range),
+                            };
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                            intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem { item: item_entity, target: None })
+                                .expect("Unable to insert intent");
+                            new_runstate = RunState::Ticking;
+                        }
+                    }
+                }
+            }
+            RunState::ShowTargeting { range, item, mut cursor } => {
+                let (result, target) = gui::ranged_target_input(&self.ecs, input, range, &mut cursor);
+                match result {
+                    ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    ItemMenuResult::NoResponse => new_runstate = RunState::ShowTargeting { range, item, cursor },
+                    ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem { item, target })
+                            .expect("Unable to insert intent");
+                        new_runstate = RunState::Ticking;
+                    }
+                }
+            }
+            RunState::ShowDropItem => {
+                let (result, item) = gui::item_menu_input(&self.ecs, input);
+                match result {
+                    ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    ItemMenuResult::NoResponse => {}
+                    ItemMenuResult::Selected => {
+                        let item_entity = item.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToDropItem { item: item_entity })
+                            .expect("Unable to insert intent");
+                        new_runstate = RunState::Ticking;
+                    }
+                }
+            }
+        }
+        {
+            let mut run_writer = self.ecs.write_resource::<RunState>();
+            *run_writer = new_runstate;
+        }
+
+        damage_system::delete_the_dead(&mut self.ecs);
+    }
+}
@@ -1,23 +1,20 @@
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB, RGBA};
-use specs::Entity;
-use super::{Rect, World};
-use std::cmp::{max, min};
-
-pub const MAPHEIGHT: usize = 43;
-pub const MAPWIDTH: usize = 80;
-pub const MAPCOUNT: usize = MAPHEIGHT * MAPWIDTH;
+use rltk::{Algorithm2D, BaseMap, Point, Rltk, RGB};
+use specs::{Entity, Join, WorldExt};
+use serde::{Serialize, Deserialize};
+use super::{Rect, World, Camera, Position, Renderable, EntryTrigger, Hidden, VIEW_WIDTH, VIEW_HEIGHT};
 
 // Adding PartialEq lets us compare two tile types to see if they match
 // that is, tile1 == tile2. I assume this means that equality on objects
 // does not normally do type matching but exact matching, so an instance
 // of one object is not normally equally to an instance of another.
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
+    DownStairs,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<TileType>,
     pub rooms: Vec<Rect>,
@@ -26,7 +23,15 @@ pub struct Map {
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
     pub blocked: Vec<bool>,
+
+    // Rebuilt every turn by the map indexing system, so it is never saved.
+    #[serde(skip_serializing, skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+    /// Tiles something stands in the way of seeing through, such as a shut door.
+    /// Rebuilt along with tile_content.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub view_blocked: Vec<bool>,
+    pub depth: i32,
 }
 
 impl Map {
@@ -35,75 +40,31 @@ impl Map {
         // We use usize such that we do not
         // return a negative idx.
         // As usize is unsigned.
-        (y as usize * MAPWIDTH) + (x as usize)
+        (y as usize * self.width as usize) + (x as usize)
     }  
 
-    /// Given a Room and a slice of Map apply the Room to the Map by
-    /// mutating it.
-    pub fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1 ..= room.y2 {
-            for x in room.x1 ..= room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
+    /// Whether a position is on the map at all.
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
     }
 
-    /// Makes a map with two rooms connected by corridors.
-    /// Returns a list of rooms.
-    pub fn new_map_rooms_and_corridors() -> Map {
-        // TODO this does not need to be in this impl.
-        let mut map = Map{
-            tiles : vec![TileType::Wall; MAPCOUNT],
+    /// Generates an empty map, consisting entirely of solid walls.
+    /// Map builders carve their layouts out of this.
+    pub fn new(width: i32, height: i32, depth: i32) -> Map {
+        let tile_count = (width * height) as usize;
+        Map {
+            tiles : vec![TileType::Wall; tile_count],
             rooms : Vec::new(),
-            width : MAPWIDTH as i32,
-            height: MAPHEIGHT as i32,
-            revealed_tiles : vec![false; MAPCOUNT],
-            visible_tiles : vec![false; MAPCOUNT],
-            blocked: vec![false; MAPCOUNT],
-            tile_content: vec![Vec::new(); MAPCOUNT],
-        };
-        
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        let mut rng = RandomNumberGenerator::new();
-
-        for _ in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, MAPWIDTH as i32 - w - 1) - 1;
-            let y = rng.roll_dice(1, MAPHEIGHT as i32 - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-            let mut ok = true;
-
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
-                    ok = false;
-                }
-            }
-            if ok {
-                map.apply_room_to_map(&new_room);
-
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-
-                    if rng.range(0, 2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-                map.rooms.push(new_room);
-            }
+            width,
+            height,
+            revealed_tiles : vec![false; tile_count],
+            visible_tiles : vec![false; tile_count],
+            blocked: vec![false; tile_count],
+            tile_content: vec![Vec::new(); tile_count],
+            view_blocked: vec![false; tile_count],
+            depth,
         }
-
-        map
-    }  
+    }
 
     /// Block a tile if it is a wall.
     pub fn populate_blocked(&mut self) {
@@ -119,24 +80,6 @@ impl Map {
         }
     }
 
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        for x in min(x1, x2) ..= max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < MAPCOUNT {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
-        }
-    }
-
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
-        for y in min(y1, y2) ..= max(y1, y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < MAPCOUNT {
-                self.tiles[idx as usize] = TileType::Floor;
-            }
-        }
-    }
-
     /// Takes an index and calculates if it can be entered.
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
         if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {
@@ -155,7 +98,7 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        self.tiles[idx] == TileType::Wall || self.view_blocked[idx]
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
@@ -203,22 +146,26 @@ impl BaseMap for Map {
     }
 }
 
-/// Given a slice of Map, and the ctx
-/// Apply a color and symbol to each idx based on the
-/// TileType.
+/// Draw the part of the map the camera can see, colouring each
+/// revealed tile by its type and greying out what isn't in view.
 pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
+    let camera = Camera::new(ecs);
+
+    for screen_y in 0..VIEW_HEIGHT {
+        for screen_x in 0..VIEW_WIDTH {
+            let world = match camera.screen_to_world(Point::new(screen_x, screen_y)) {
+                Some(world) if map.in_bounds(world.x, world.y) => world,
+                _ => continue,
+            };
+            let idx = map.xy_idx(world.x, world.y);
+            if !map.revealed_tiles[idx] {
+                continue;
+            }
 
-    let mut y = 0;
-    let mut x = 0;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending upon tile type
-
-        if map.revealed_tiles[idx] {
             let glyph;
             let mut fg;
-            
-            match tile {
+            match map.tiles[idx] {
                 TileType::Floor => {
                     fg = RGB::from_f32(0.0, 0.5, 0.5);
                     glyph = rltk::to_cp437('.');
@@ -227,19 +174,67 @@ pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
                     fg = RGB::from_f32(0.0, 1.0, 0.0);
                     glyph = rltk::to_cp437('#');
                 }
+                TileType::DownStairs => {
+                    fg = RGB::from_f32(0.0, 1.0, 1.0);
+                    glyph = rltk::to_cp437('>');
+                }
             }
             if !map.visible_tiles[idx] {
                 fg = fg.to_greyscale()
             }
-            ctx.set(x, y, fg, RGB::from_f32(0.0, 0.0, 0.0), glyph);
-
+            ctx.set(screen_x, screen_y, fg, RGB::from_f32(0.0, 0.0, 0.0), glyph);
         }
+    }
 
-        // Move the coordinates
-        x += 1;
-        if x > 79 {
-            x = 0;
-            y += 1;
+    // Traps the player has found are part of what they know about the
+    // map, so they stay drawn, greyed out, once out of sight. Those in
+    // view are drawn with everything else, under whoever stands on them.
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    for trap in known_traps(ecs) {
+        let (Some(pos), Some(render)) = (positions.get(trap), renderables.get(trap)) else {
+            continue;
+        };
+        let idx = map.xy_idx(pos.x, pos.y);
+        if map.visible_tiles[idx] {
+            continue;
+        }
+        if let Some(screen) = camera.world_to_screen(Point::new(pos.x, pos.y)) {
+            ctx.set(screen.x, screen.y, render.fg.to_greyscale(), render.bg, render.glyph);
         }
     }
-}
\ No newline at end of file
+}
+
+/// The entities in view, in the order they should be drawn, so that
+/// on a shared tile the one with the lowest render order ends up on top.
+pub fn entities_to_draw(ecs: &World) -> Vec<(Point, Renderable)> {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let hidden = ecs.read_storage::<Hidden>();
+
+    // Join these two components.
+    // Literally a union.
+    // It's implicit Union, but it works, as each Entity already has a
+    // unique id tied to it from the build step.
+    let mut data: Vec<(Point, Renderable)> = (&positions, &renderables, !&hidden).join()
+        .filter(|(pos, _render, _hidden)| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(pos, render, _hidden)| (Point::new(pos.x, pos.y), render.clone()))
+        .collect();
+    data.sort_by_key(|(_pos, render)| std::cmp::Reverse(render.render_order));
+    data
+}
+
+/// Traps the player has found, whether or not they have gone off,
+/// on tiles the player has seen.
+pub fn known_traps(ecs: &World) -> Vec<Entity> {
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let hidden = ecs.read_storage::<Hidden>();
+    (&entities, &positions, &triggers, !&hidden).join()
+        .filter(|(_entity, pos, _trigger, _hidden)| map.revealed_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(entity, _pos, _trigger, _hidden)| entity)
+        .collect()
+}
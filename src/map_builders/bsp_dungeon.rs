@@ -0,0 +1,145 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use super::{
+    MapBuilder, Map, Rect, TileType, Position, spawner,
+    apply_room_to_map, apply_horizontal_tunnel, apply_vertical_tunnel, door_positions
+};
+
+/// Smallest width or height a partition can be split down to.
+const MIN_LEAF: i32 = 10;
+/// Partitions no bigger than this in either direction may stop splitting early.
+const MAX_LEAF: i32 = 20;
+const MIN_ROOM: i32 = 4;
+const MAX_ROOM: i32 = 12;
+
+/// Binary space partition builder.
+/// Recursively splits the map in two until the pieces are small, puts a
+/// room in every leaf, then joins each pair of siblings with a corridor
+/// on the way back up. Every part of the map ends up with a room in it.
+pub struct BspDungeonBuilder {
+    map: Map,
+    starting_position: Position,
+}
+
+impl MapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+
+        // Partitions use an exclusive x2/y2, so this is the whole map.
+        let whole_map = Rect::new(0, 0, self.map.width, self.map.height);
+        self.partition(&whole_map, rng);
+
+        // The way down is in the middle of the last room.
+        let (stairs_x, stairs_y) = self.map.rooms[self.map.rooms.len() - 1].center();
+        let stairs_idx = self.map.xy_idx(stairs_x, stairs_y);
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+
+        let (start_x, start_y) = self.map.rooms[0].center();
+        self.starting_position = Position { x: start_x, y: start_y };
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        // The first room is where the player starts, so leave it empty.
+        for room in self.map.rooms.iter().skip(1) {
+            spawner::spawn_room(ecs, &self.map, room);
+        }
+        for idx in door_positions(&self.map) {
+            spawner::spawn_door(ecs, &self.map, idx);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+}
+
+impl BspDungeonBuilder {
+    pub fn new(width: i32, height: i32, new_depth: i32) -> BspDungeonBuilder {
+        BspDungeonBuilder {
+            map: Map::new(width, height, new_depth),
+            starting_position: Position { x: 0, y: 0 },
+        }
+    }
+
+    /// Split an area in two and recurse into both halves, or fill it with a
+    /// room if it is a leaf. Returns one of the rooms inside the area so that
+    /// the caller can connect it to the area's sibling.
+    fn partition(&mut self, area: &Rect, rng: &mut RandomNumberGenerator) -> Rect {
+        let width = area.x2 - area.x1;
+        let height = area.y2 - area.y1;
+        let can_split_x = width >= MIN_LEAF * 2;
+        let can_split_y = height >= MIN_LEAF * 2;
+        let small_enough = width <= MAX_LEAF && height <= MAX_LEAF;
+
+        if (!can_split_x && !can_split_y) || (small_enough && rng.roll_dice(1, 4) == 1) {
+            return self.add_room(area, rng);
+        }
+
+        // Prefer cutting across the longer side so leaves stay roughly square.
+        let split_x = if !can_split_y {
+            true
+        } else if !can_split_x {
+            false
+        } else if width as f32 >= height as f32 * 1.25 {
+            true
+        } else if height as f32 >= width as f32 * 1.25 {
+            false
+        } else {
+            rng.range(0, 2) == 1
+        };
+
+        let (first, second) = if split_x {
+            let split = rng.range(MIN_LEAF, width - MIN_LEAF + 1);
+            (
+                Rect::new(area.x1, area.y1, split, height),
+                Rect::new(area.x1 + split, area.y1, width - split, height),
+            )
+        } else {
+            let split = rng.range(MIN_LEAF, height - MIN_LEAF + 1);
+            (
+                Rect::new(area.x1, area.y1, width, split),
+                Rect::new(area.x1, area.y1 + split, width, height - split),
+            )
+        };
+
+        let first_room = self.partition(&first, rng);
+        let second_room = self.partition(&second, rng);
+        self.connect(&first_room, &second_room, rng);
+
+        if rng.range(0, 2) == 1 { first_room } else { second_room }
+    }
+
+    /// Place a randomly sized room inside a leaf, leaving a wall between it
+    /// and the edge of the leaf so that neighbouring rooms never merge.
+    fn add_room(&mut self, area: &Rect, rng: &mut RandomNumberGenerator) -> Rect {
+        let width = area.x2 - area.x1;
+        let height = area.y2 - area.y1;
+
+        // A room carves x1..=x2 and y1+1..=y2, hence the uneven margins.
+        let w = rng.range(MIN_ROOM, i32::min(MAX_ROOM, width - 3) + 1);
+        let h = rng.range(MIN_ROOM, i32::min(MAX_ROOM, height - 2) + 1);
+        let x = area.x1 + 1 + rng.range(0, width - 3 - w + 1);
+        let y = area.y1 + rng.range(0, height - 2 - h + 1);
+
+        let room = Rect::new(x, y, w, h);
+        apply_room_to_map(&mut self.map, &room);
+        self.map.rooms.push(room);
+        room
+    }
+
+    fn connect(&mut self, first: &Rect, second: &Rect, rng: &mut RandomNumberGenerator) {
+        let (first_x, first_y) = first.center();
+        let (second_x, second_y) = second.center();
+
+        if rng.range(0, 2) == 1 {
+            apply_horizontal_tunnel(&mut self.map, first_x, second_x, first_y);
+            apply_vertical_tunnel(&mut self.map, first_y, second_y, second_x);
+        } else {
+            apply_vertical_tunnel(&mut self.map, first_y, second_y, first_x);
+            apply_horizontal_tunnel(&mut self.map, first_x, second_x, second_y);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use super::{
+    MapBuilder, Map, TileType, Position, spawner,
+    remove_unreachable_areas_returning_most_distant, generate_voronoi_spawn_regions
+};
+
+/// How many smoothing passes to run over the initial noise.
+const ITERATIONS: i32 = 15;
+
+/// Organic cave builder.
+/// Starts from random noise and repeatedly smooths it: a tile becomes
+/// a wall when most of its neighbours are walls, and a floor otherwise.
+pub struct CellularAutomataBuilder {
+    map: Map,
+    starting_position: Position,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+
+        // Start with roughly 55% floor, keeping a solid border.
+        for y in 1 .. self.map.height - 1 {
+            for x in 1 .. self.map.width - 1 {
+                let roll = rng.roll_dice(1, 100);
+                let idx = self.map.xy_idx(x, y);
+                if roll > 55 {
+                    self.map.tiles[idx] = TileType::Floor
+                } else {
+                    self.map.tiles[idx] = TileType::Wall
+                }
+            }
+        }
+
+        for _ in 0..ITERATIONS {
+            self.smooth();
+        }
+
+        // Start as close to the middle as we can, then walk left until we find floor.
+        self.starting_position = Position { x: self.map.width / 2, y: self.map.height / 2 };
+        let mut start_idx = self.map.xy_idx(self.starting_position.x, self.starting_position.y);
+        while self.map.tiles[start_idx] != TileType::Floor {
+            self.starting_position.x -= 1;
+            start_idx = self.map.xy_idx(self.starting_position.x, self.starting_position.y);
+        }
+
+        // Caves can be cut off from one another; get rid of anything we can't
+        // walk to, and put the exit as far from the start as possible.
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        // Don't spawn anything on top of the player.
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+        for area in self.noise_areas.values_mut() {
+            area.retain(|idx| *idx != start_idx);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        for area in self.noise_areas.values() {
+            spawner::spawn_region(ecs, &self.map, area);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+}
+
+impl CellularAutomataBuilder {
+    pub fn new(width: i32, height: i32, new_depth: i32) -> CellularAutomataBuilder {
+        CellularAutomataBuilder {
+            map: Map::new(width, height, new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            noise_areas: BTreeMap::new(),
+        }
+    }
+
+    /// One pass of the automaton. Every tile is decided from the previous
+    /// pass, so the new tiles are written to a copy.
+    fn smooth(&mut self) {
+        let mut new_tiles = self.map.tiles.clone();
+
+        for y in 1 .. self.map.height - 1 {
+            for x in 1 .. self.map.width - 1 {
+                let idx = self.map.xy_idx(x, y);
+                let mut neighbors = 0;
+                for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    if self.map.tiles[self.map.xy_idx(x + dx, y + dy)] == TileType::Wall {
+                        neighbors += 1;
+                    }
+                }
+
+                // Crowded tiles fill in, and so do isolated ones so
+                // that we don't end up with lone pillars everywhere.
+                if neighbors > 4 || neighbors == 0 {
+                    new_tiles[idx] = TileType::Wall;
+                } else {
+                    new_tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+
+        self.map.tiles = new_tiles;
+    }
+}
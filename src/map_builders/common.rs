@@ -0,0 +1,188 @@
+use rltk::RandomNumberGenerator;
+use super::{Map, Rect, TileType};
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+
+/// Given a Room and a slice of Map apply the Room to the Map by
+/// mutating it.
+pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1 ..= room.y2 {
+        for x in room.x1 ..= room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+    for x in min(x1, x2) ..= max(x1, x2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.tiles.len() {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_vertical_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2) ..= max(y1, y2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.tiles.len() {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+/// Flood fills the map from the start tile, walls off every floor
+/// tile that cannot be reached, and returns the index of the
+/// reachable tile furthest away from the start.
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.populate_blocked();
+    let map_starts: Vec<usize> = vec![start_idx];
+    // Anything past the search's maximum depth would be walled off as
+    // unreachable, and no path can be longer than the map has tiles.
+    let max_depth = (map.width * map.height) as f32;
+    let dijkstra_map = rltk::DijkstraMap::new(map.width, map.height, &map_starts, &*map, max_depth);
+
+    let mut exit_tile = (0, 0.0f32);
+    for (i, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor {
+            let distance_to_start = dijkstra_map.map[i];
+            // We can't get to this tile, so wall it off.
+            if distance_to_start == f32::MAX {
+                *tile = TileType::Wall;
+            } else if distance_to_start > exit_tile.1 {
+                exit_tile.0 = i;
+                exit_tile.1 = distance_to_start;
+            }
+        }
+    }
+
+    exit_tile.0
+}
+
+/// Carves the map up into noise regions, roughly Voronoi cells, listing the
+/// floor tiles in each. Maps without rooms spawn entities per region.
+pub fn generate_voronoi_spawn_regions(map: &Map, rng: &mut RandomNumberGenerator) -> BTreeMap<i32, Vec<usize>> {
+    // A BTreeMap keeps the regions in a stable order, unlike a HashMap.
+    let mut noise_areas: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    for y in 1 .. map.height - 1 {
+        for x in 1 .. map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                let cell_value = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+                noise_areas.entry(cell_value).or_default().push(idx);
+            }
+        }
+    }
+
+    noise_areas
+}
+
+/// Mirrors everything a builder paints across the
+/// middle of the map, in one or both directions.
+#[derive(PartialEq, Copy, Clone)]
+pub enum Symmetry {
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+/// Turn the tiles under a brush into floor, repeating
+/// the stroke on the other side of any lines of symmetry.
+pub fn paint(map: &mut Map, mode: Symmetry, brush_size: i32, x: i32, y: i32) {
+    let center_x = map.width / 2;
+    let center_y = map.height / 2;
+    let dist_x = i32::abs(center_x - x);
+    let dist_y = i32::abs(center_y - y);
+
+    match mode {
+        Symmetry::None => apply_paint(map, brush_size, x, y),
+        Symmetry::Horizontal => {
+            apply_paint(map, brush_size, center_x + dist_x, y);
+            apply_paint(map, brush_size, center_x - dist_x, y);
+        }
+        Symmetry::Vertical => {
+            apply_paint(map, brush_size, x, center_y + dist_y);
+            apply_paint(map, brush_size, x, center_y - dist_y);
+        }
+        Symmetry::Both => {
+            apply_paint(map, brush_size, center_x + dist_x, center_y + dist_y);
+            apply_paint(map, brush_size, center_x - dist_x, center_y + dist_y);
+            apply_paint(map, brush_size, center_x + dist_x, center_y - dist_y);
+            apply_paint(map, brush_size, center_x - dist_x, center_y - dist_y);
+        }
+    }
+}
+
+fn apply_paint(map: &mut Map, brush_size: i32, x: i32, y: i32) {
+    if brush_size == 1 {
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Floor;
+        return;
+    }
+
+    // Larger brushes never touch the outer walls of the map.
+    let half_brush_size = brush_size / 2;
+    for brush_y in y - half_brush_size .. y + half_brush_size {
+        for brush_x in x - half_brush_size .. x + half_brush_size {
+            if brush_x > 1 && brush_x < map.width - 1 && brush_y > 1 && brush_y < map.height - 1 {
+                let idx = map.xy_idx(brush_x, brush_y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+}
+
+/// Finds the doorways into each room: floor tiles just outside its
+/// edge with wall on either side, where a corridor meets the room.
+/// Tiles next to a doorway already picked are left alone.
+pub fn door_positions(map: &Map) -> Vec<usize> {
+    let is_wall = |x: i32, y: i32| !map.in_bounds(x, y) || map.tiles[map.xy_idx(x, y)] == TileType::Wall;
+    let mut doors: Vec<usize> = Vec::new();
+
+    for room in map.rooms.iter() {
+        // Each candidate carries the direction the walls have to be in,
+        // across the way into the room rather than along it.
+        let mut candidates: Vec<(i32, i32, bool)> = Vec::new();
+        for y in room.y1 + 1 ..= room.y2 {
+            candidates.push((room.x1 - 1, y, false));
+            candidates.push((room.x2 + 1, y, false));
+        }
+        for x in room.x1 ..= room.x2 {
+            candidates.push((x, room.y1, true));
+            candidates.push((x, room.y2 + 1, true));
+        }
+
+        for (x, y, walls_beside) in candidates {
+            if !map.in_bounds(x, y) || map.tiles[map.xy_idx(x, y)] != TileType::Floor {
+                continue;
+            }
+            let walled = if walls_beside {
+                is_wall(x - 1, y) && is_wall(x + 1, y)
+            } else {
+                is_wall(x, y - 1) && is_wall(x, y + 1)
+            };
+            let idx = map.xy_idx(x, y);
+            let crowded = doors.iter().any(|door| {
+                let (door_x, door_y) = (*door as i32 % map.width, *door as i32 / map.width);
+                i32::abs(door_x - x) <= 1 && i32::abs(door_y - y) <= 1
+            });
+            if walled && !crowded {
+                doors.push(idx);
+            }
+        }
+    }
+
+    doors
+}
+
+/// Number of floor tiles on the map.
+pub fn count_floor_tiles(map: &Map) -> usize {
+    map.tiles.iter().filter(|tile| **tile == TileType::Floor).count()
+}
@@ -0,0 +1,211 @@
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use super::{
+    MapBuilder, Map, TileType, Position, spawner, Symmetry, paint, count_floor_tiles,
+    remove_unreachable_areas_returning_most_distant, generate_voronoi_spawn_regions
+};
+
+/// How particles find their way to the existing floor.
+#[derive(PartialEq, Copy, Clone)]
+pub enum DLAAlgorithm {
+    /// Particles start anywhere and wander until they bump into floor.
+    WalkInwards,
+    /// Particles start in the middle and wander until they leave the floor.
+    WalkOutwards,
+    /// Particles start anywhere and head straight for the middle.
+    CentralAttractor,
+}
+
+pub struct DLASettings {
+    pub algorithm: DLAAlgorithm,
+    pub brush_size: i32,
+    pub symmetry: Symmetry,
+    /// Fraction of the map that should end up as floor.
+    pub floor_percent: f32,
+}
+
+/// Diffusion-limited aggregation builder.
+/// Grows a structure from a seed in the middle of the map by sticking
+/// wandering particles onto it wherever they first touch it.
+pub struct DLABuilder {
+    map: Map,
+    starting_position: Position,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+    settings: DLASettings,
+}
+
+impl MapBuilder for DLABuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+
+        // Carve a small seed in the middle of the map for everything to grow from.
+        self.starting_position = Position { x: self.map.width / 2, y: self.map.height / 2 };
+        let start_idx = self.map.xy_idx(self.starting_position.x, self.starting_position.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+        self.map.tiles[start_idx - 1] = TileType::Floor;
+        self.map.tiles[start_idx + 1] = TileType::Floor;
+        self.map.tiles[start_idx - self.map.width as usize] = TileType::Floor;
+        self.map.tiles[start_idx + self.map.width as usize] = TileType::Floor;
+
+        let total_tiles = self.map.width * self.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = count_floor_tiles(&self.map);
+
+        while floor_tile_count < desired_floor_tiles {
+            match self.settings.algorithm {
+                DLAAlgorithm::WalkInwards => self.add_particle_walking_inwards(rng),
+                DLAAlgorithm::WalkOutwards => self.add_particle_walking_outwards(rng),
+                DLAAlgorithm::CentralAttractor => self.add_particle_attracted_to_center(rng),
+            }
+            floor_tile_count = count_floor_tiles(&self.map);
+        }
+
+        // Symmetry can paint pockets that the particles never joined up;
+        // remove them and put the exit as far away as possible.
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        // Don't spawn anything on top of the player.
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+        for area in self.noise_areas.values_mut() {
+            area.retain(|idx| *idx != start_idx);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        for area in self.noise_areas.values() {
+            spawner::spawn_region(ecs, &self.map, area);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+}
+
+impl DLABuilder {
+    pub fn new(width: i32, height: i32, new_depth: i32, settings: DLASettings) -> DLABuilder {
+        DLABuilder {
+            map: Map::new(width, height, new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            noise_areas: BTreeMap::new(),
+            settings,
+        }
+    }
+
+    pub fn walk_inwards(width: i32, height: i32, new_depth: i32) -> DLABuilder {
+        DLABuilder::new(width, height, new_depth, DLASettings {
+            algorithm: DLAAlgorithm::WalkInwards,
+            brush_size: 1,
+            symmetry: Symmetry::None,
+            floor_percent: 0.25,
+        })
+    }
+
+    pub fn walk_outwards(width: i32, height: i32, new_depth: i32) -> DLABuilder {
+        DLABuilder::new(width, height, new_depth, DLASettings {
+            algorithm: DLAAlgorithm::WalkOutwards,
+            brush_size: 2,
+            symmetry: Symmetry::None,
+            floor_percent: 0.25,
+        })
+    }
+
+    pub fn central_attractor(width: i32, height: i32, new_depth: i32) -> DLABuilder {
+        DLABuilder::new(width, height, new_depth, DLASettings {
+            algorithm: DLAAlgorithm::CentralAttractor,
+            brush_size: 2,
+            symmetry: Symmetry::None,
+            floor_percent: 0.25,
+        })
+    }
+
+    /// A central attractor mirrored left to right, which tends to look like a bug.
+    pub fn insectoid(width: i32, height: i32, new_depth: i32) -> DLABuilder {
+        DLABuilder::new(width, height, new_depth, DLASettings {
+            algorithm: DLAAlgorithm::CentralAttractor,
+            brush_size: 2,
+            symmetry: Symmetry::Horizontal,
+            floor_percent: 0.25,
+        })
+    }
+
+    /// A central attractor mirrored top to bottom, like an ink blot.
+    pub fn rorschach(width: i32, height: i32, new_depth: i32) -> DLABuilder {
+        DLABuilder::new(width, height, new_depth, DLASettings {
+            algorithm: DLAAlgorithm::CentralAttractor,
+            brush_size: 2,
+            symmetry: Symmetry::Vertical,
+            floor_percent: 0.25,
+        })
+    }
+
+    /// Move a particle one step in a random direction, staying off the outer walls.
+    fn stagger(&self, x: &mut i32, y: &mut i32, rng: &mut RandomNumberGenerator) {
+        match rng.roll_dice(1, 4) {
+            1 => if *x > 2 { *x -= 1; }
+            2 => if *x < self.map.width - 2 { *x += 1; }
+            3 => if *y > 2 { *y -= 1; }
+            _ => if *y < self.map.height - 2 { *y += 1; }
+        }
+    }
+
+    fn add_particle_walking_inwards(&mut self, rng: &mut RandomNumberGenerator) {
+        let mut digger_x = rng.roll_dice(1, self.map.width - 3) + 1;
+        let mut digger_y = rng.roll_dice(1, self.map.height - 3) + 1;
+        let mut prev_x = digger_x;
+        let mut prev_y = digger_y;
+
+        // Wander until we hit floor, then dig out the last wall we were on.
+        let mut digger_idx = self.map.xy_idx(digger_x, digger_y);
+        while self.map.tiles[digger_idx] == TileType::Wall {
+            prev_x = digger_x;
+            prev_y = digger_y;
+            self.stagger(&mut digger_x, &mut digger_y, rng);
+            digger_idx = self.map.xy_idx(digger_x, digger_y);
+        }
+        paint(&mut self.map, self.settings.symmetry, self.settings.brush_size, prev_x, prev_y);
+    }
+
+    fn add_particle_walking_outwards(&mut self, rng: &mut RandomNumberGenerator) {
+        let mut digger_x = self.starting_position.x;
+        let mut digger_y = self.starting_position.y;
+
+        // Wander until we step off the floor, then dig out where we ended up.
+        let mut digger_idx = self.map.xy_idx(digger_x, digger_y);
+        while self.map.tiles[digger_idx] == TileType::Floor {
+            self.stagger(&mut digger_x, &mut digger_y, rng);
+            digger_idx = self.map.xy_idx(digger_x, digger_y);
+        }
+        paint(&mut self.map, self.settings.symmetry, self.settings.brush_size, digger_x, digger_y);
+    }
+
+    fn add_particle_attracted_to_center(&mut self, rng: &mut RandomNumberGenerator) {
+        let mut digger_x = rng.roll_dice(1, self.map.width - 3) + 1;
+        let mut digger_y = rng.roll_dice(1, self.map.height - 3) + 1;
+        let mut prev_x = digger_x;
+        let mut prev_y = digger_y;
+
+        // Head straight for the middle until we hit floor.
+        let mut path = rltk::line2d(
+            rltk::LineAlg::Bresenham,
+            Point::new(digger_x, digger_y),
+            Point::new(self.starting_position.x, self.starting_position.y)
+        );
+
+        let mut digger_idx = self.map.xy_idx(digger_x, digger_y);
+        while self.map.tiles[digger_idx] == TileType::Wall && !path.is_empty() {
+            prev_x = digger_x;
+            prev_y = digger_y;
+            digger_x = path[0].x;
+            digger_y = path[0].y;
+            path.remove(0);
+            digger_idx = self.map.xy_idx(digger_x, digger_y);
+        }
+        paint(&mut self.map, self.settings.symmetry, self.settings.brush_size, prev_x, prev_y);
+    }
+}
@@ -0,0 +1,157 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use super::{
+    MapBuilder, Map, TileType, Position, spawner, Symmetry, paint, count_floor_tiles,
+    remove_unreachable_areas_returning_most_distant, generate_voronoi_spawn_regions
+};
+
+/// Where each new digger starts stumbling from.
+#[derive(PartialEq, Copy, Clone)]
+pub enum DrunkSpawnMode {
+    StartingPoint,
+    Random,
+}
+
+pub struct DrunkardSettings {
+    pub spawn_mode: DrunkSpawnMode,
+    /// How many steps a digger takes before it gives up.
+    pub drunken_lifetime: i32,
+    /// Fraction of the map that should end up as floor.
+    pub floor_percent: f32,
+    pub brush_size: i32,
+    pub symmetry: Symmetry,
+}
+
+/// Drunkard's walk builder.
+/// Sends diggers out to stagger around at random, turning everything
+/// they step on into floor, until enough of the map has been dug out.
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    starting_position: Position,
+    noise_areas: BTreeMap<i32, Vec<usize>>,
+    settings: DrunkardSettings,
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+
+        // Always dig out from the middle of the map.
+        self.starting_position = Position { x: self.map.width / 2, y: self.map.height / 2 };
+        let start_idx = self.map.xy_idx(self.starting_position.x, self.starting_position.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        let total_tiles = self.map.width * self.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = count_floor_tiles(&self.map);
+        let mut digger_count = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            let (mut drunk_x, mut drunk_y) = match self.settings.spawn_mode {
+                DrunkSpawnMode::Random if digger_count > 0 => (
+                    rng.roll_dice(1, self.map.width - 3) + 1,
+                    rng.roll_dice(1, self.map.height - 3) + 1,
+                ),
+                _ => (self.starting_position.x, self.starting_position.y),
+            };
+
+            let mut drunk_life = self.settings.drunken_lifetime;
+            while drunk_life > 0 {
+                paint(&mut self.map, self.settings.symmetry, self.settings.brush_size, drunk_x, drunk_y);
+
+                match rng.roll_dice(1, 4) {
+                    1 => if drunk_x > 2 { drunk_x -= 1; }
+                    2 => if drunk_x < self.map.width - 2 { drunk_x += 1; }
+                    3 => if drunk_y > 2 { drunk_y -= 1; }
+                    _ => if drunk_y < self.map.height - 2 { drunk_y += 1; }
+                }
+
+                drunk_life -= 1;
+            }
+
+            digger_count += 1;
+            floor_tile_count = count_floor_tiles(&self.map);
+        }
+
+        // Diggers that started at random may have dug out disconnected
+        // pockets; remove them and put the exit as far away as possible.
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+        self.map.tiles[exit_idx] = TileType::DownStairs;
+
+        // Don't spawn anything on top of the player.
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+        for area in self.noise_areas.values_mut() {
+            area.retain(|idx| *idx != start_idx);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        for area in self.noise_areas.values() {
+            spawner::spawn_region(ecs, &self.map, area);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position.clone()
+    }
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(width: i32, height: i32, new_depth: i32, settings: DrunkardSettings) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder {
+            map: Map::new(width, height, new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            noise_areas: BTreeMap::new(),
+            settings,
+        }
+    }
+
+    /// Long-lived diggers from the middle of the map make one big open space.
+    pub fn open_halls(width: i32, height: i32, new_depth: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(width, height, new_depth, DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::StartingPoint,
+            drunken_lifetime: 400,
+            floor_percent: 0.5,
+            brush_size: 1,
+            symmetry: Symmetry::None,
+        })
+    }
+
+    /// Short-lived diggers from all over the map make lots of narrow passages.
+    pub fn narrow_passages(width: i32, height: i32, new_depth: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(width, height, new_depth, DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 100,
+            floor_percent: 0.4,
+            brush_size: 1,
+            symmetry: Symmetry::None,
+        })
+    }
+
+    /// Very short-lived diggers and a low floor target leave long winding paths.
+    pub fn winding_passages(width: i32, height: i32, new_depth: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(width, height, new_depth, DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::Random,
+            drunken_lifetime: 100,
+            floor_percent: 0.3,
+            brush_size: 1,
+            symmetry: Symmetry::None,
+        })
+    }
+
+    /// Diggers mirrored in both directions. They all leave from the middle
+    /// so that every mirrored copy of a path stays connected to the start.
+    pub fn fearful_symmetry(width: i32, height: i32, new_depth: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder::new(width, height, new_depth, DrunkardSettings {
+            spawn_mode: DrunkSpawnMode::StartingPoint,
+            drunken_lifetime: 100,
+            floor_percent: 0.4,
+            brush_size: 1,
+            symmetry: Symmetry::Both,
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use super::{Map, Rect, TileType, Position, spawner};
+mod common;
+use common::*;
+mod simple_map;
+use simple_map::SimpleMapBuilder;
+mod bsp_dungeon;
+use bsp_dungeon::BspDungeonBuilder;
+mod cellular_automata;
+use cellular_automata::CellularAutomataBuilder;
+mod drunkard;
+use drunkard::DrunkardsWalkBuilder;
+mod dla;
+use dla::DLABuilder;
+
+/// A map generation algorithm.
+/// Builders carve out a map, then populate it once it
+/// has been inserted alongside the rest of the world.
+/// All of their randomness comes from the rng they are handed,
+/// so that a seeded game always produces the same maps.
+pub trait MapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+    fn spawn_entities(&mut self, ecs: &mut World);
+    fn get_map(&self) -> Map;
+    fn get_starting_position(&self) -> Position;
+}
+
+/// Every builder that can be asked for by name.
+pub const BUILDER_NAMES: &[&str] = &[
+    "simple",
+    "bsp",
+    "cellular_automata",
+    "drunkard_open_halls",
+    "drunkard_narrow_passages",
+    "drunkard_winding_passages",
+    "drunkard_fearful_symmetry",
+    "dla_walk_inwards",
+    "dla_walk_outwards",
+    "dla_central_attractor",
+    "dla_insectoid",
+    "dla_rorschach",
+];
+
+/// Width and height of maps made of rooms and corridors.
+const DUNGEON_SIZE: (i32, i32) = (96, 54);
+
+/// Caves sprawl, so they get more room than dungeons do.
+const CAVERN_SIZE: (i32, i32) = (112, 64);
+
+/// Pick one of the available builders at random.
+pub fn random_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    let roll = rng.roll_dice(1, BUILDER_NAMES.len() as i32) - 1;
+    builder_by_name(BUILDER_NAMES[roll as usize], new_depth).unwrap()
+}
+
+/// Look up a builder by its name, e.g. "simple".
+pub fn builder_by_name(name: &str, new_depth: i32) -> Option<Box<dyn MapBuilder>> {
+    let (dw, dh) = DUNGEON_SIZE;
+    let (cw, ch) = CAVERN_SIZE;
+    match name {
+        "simple" => Some(Box::new(SimpleMapBuilder::new(dw, dh, new_depth))),
+        "bsp" => Some(Box::new(BspDungeonBuilder::new(dw, dh, new_depth))),
+        "cellular_automata" => Some(Box::new(CellularAutomataBuilder::new(cw, ch, new_depth))),
+        "drunkard_open_halls" => Some(Box::new(DrunkardsWalkBuilder::open_halls(cw, ch, new_depth))),
+        "drunkard_narrow_passages" => Some(Box::new(DrunkardsWalkBuilder::narrow_passages(cw, ch, new_depth))),
+        "drunkard_winding_passages" => Some(Box::new(DrunkardsWalkBuilder::winding_passages(cw, ch, new_depth))),
+        "drunkard_fearful_symmetry" => Some(Box::new(DrunkardsWalkBuilder::fearful_symmetry(cw, ch, new_depth))),
+        "dla_walk_inwards" => Some(Box::new(DLABuilder::walk_inwards(dw, dh, new_depth))),
+        "dla_walk_outwards" => Some(Box::new(DLABuilder::walk_outwards(dw, dh, new_depth))),
+        "dla_central_attractor" => Some(Box::new(DLABuilder::central_attractor(dw, dh, new_depth))),
+        "dla_insectoid" => Some(Box::new(DLABuilder::insectoid(dw, dh, new_depth))),
+        "dla_rorschach" => Some(Box::new(DLABuilder::rorschach(dw, dh, new_depth))),
+        _ => None,
+    }
+}
@@ -1,5 +1,5 @@
 use specs::prelude::*;
-use super::{Map, Position, BlocksTile};
+use super::{Map, Position, BlocksTile, BlocksVisibility};
 
 pub struct MapIndexingSystem {}
 
@@ -8,11 +8,12 @@ impl <'a> System<'a> for MapIndexingSystem {
         WriteExpect<'a, Map>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, BlocksVisibility>,
         Entities<'a>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, sight_blockers, entities) = data;
 
         // Initial population of blocked tiled.
         map.populate_blocked();
@@ -20,16 +21,22 @@ impl <'a> System<'a> for MapIndexingSystem {
         // Clear all entities that are in the maps list of known
         // tile content.
         map.clear_content_index();
-        
+
+        // Only walls block sight until an entity says otherwise.
+        map.view_blocked.fill(false);
+
         for (position, entity) in (&position, &entities).join() {
             let idx = map.xy_idx(position.x, position.y);
 
             // Check to see if an entity has a BlocksTile component.
             // if it does, update the maps blocked list.
             let associated_blockers: Option<&BlocksTile> = blockers.get(entity);
-            if let Some(_) = associated_blockers {
+            if associated_blockers.is_some() {
                 map.blocked[idx] = true;
             }
+            if sight_blockers.get(entity).is_some() {
+                map.view_blocked[idx] = true;
+            }
 
             // Push the entity to the appropriate index slot. It's a copy
             // type, so we don't need to clone it (we want to avoid moving it out of the ECS!)
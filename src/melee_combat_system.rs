@@ -1,6 +1,9 @@
-use rltk::console;
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
-use super::{CombatStats, WantsToMelee, Name, SufferDamage, GameLog};
+use super::{
+    CombatStats, Attributes, WantsToMelee, Name, SufferDamage, GameLog, MeleeWeapon, DefenseBonus, Equipped,
+    melee_bonus, armor_class
+};
 
 pub struct MeleeCombatSystem {}
 
@@ -8,27 +11,76 @@ impl<'a> System<'a> for MeleeCombatSystem {
     type SystemData = (
         Entities<'a>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
-        WriteStorage<'a, SufferDamage>
+        ReadStorage<'a, Attributes>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, MeleeWeapon>,
+        ReadStorage<'a, DefenseBonus>,
+        ReadStorage<'a, Equipped>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut log, mut wants_melee, names, combat_stats, mut inflict_damage) = data;
+        let (
+            entities,
+            mut log,
+            mut rng,
+            mut wants_melee,
+            names,
+            combat_stats,
+            attributes,
+            mut inflict_damage,
+            melee_weapons,
+            defense_bonuses,
+            equipped
+        ) = data;
 
-        for (_entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
+        for (entity, wants_melee, name, stats, attacker_attributes) in (&entities, &wants_melee, &names, &combat_stats, &attributes).join() {
             if stats.hp > 0 {
-                let target_stats = combat_stats.get(wants_melee.target).unwrap();
+                // Anything without stats to defend with can't be fought.
+                let (Some(target_stats), Some(target_attributes)) =
+                    (combat_stats.get(wants_melee.target), attributes.get(wants_melee.target))
+                else {
+                    continue;
+                };
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
-                    let damage = i32::max(0, stats.power - target_stats.defense);
 
-                    if damage == 0 {
-                        log.entries.push(format!("{} is unable to hurt {}", &name.name, &target_name.name));
+                    // A wielded weapon replaces the attacker's own damage.
+                    let bonus = melee_bonus(attacker_attributes);
+                    let mut damage_dice = stats.damage;
+                    let mut to_hit = bonus;
+                    for (_item_entity, weapon, equipped_by) in (&entities, &melee_weapons, &equipped).join() {
+                        if equipped_by.owner == entity {
+                            damage_dice = weapon.damage;
+                            to_hit += weapon.to_hit;
+                        }
+                    }
+                    let mut armor_class = armor_class(target_attributes);
+                    for (_item_entity, defense_bonus, equipped_by) in (&entities, &defense_bonuses, &equipped).join() {
+                        if equipped_by.owner == wants_melee.target {
+                            armor_class += defense_bonus.defense;
+                        }
+                    }
+
+                    // A natural 1 always misses and a natural 20 always hits,
+                    // rolling the damage dice twice, whatever the armor class.
+                    // The attacker's melee bonus adds to both rolls.
+                    let natural_roll = rng.roll_dice(1, 20);
+                    if natural_roll == 1 {
+                        log.entries.push(format!("{} fumbles the attack on {}", &name.name, &target_name.name));
+                    } else if natural_roll == 20 {
+                        let damage = i32::max(1, rng.roll(damage_dice) + rng.roll(damage_dice) + bonus);
+                        log.entries.push(format!("{} critically hits {}, for {} hp", &name.name, &target_name.name, damage));
+                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, entity);
+                    } else if natural_roll + to_hit < armor_class {
+                        log.entries.push(format!("{} misses {}", &name.name, &target_name.name));
                     } else {
+                        let damage = i32::max(1, rng.roll(damage_dice) + bonus);
                         log.entries.push(format!("{} hits {}, for {} hp", &name.name, &target_name.name, damage));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, entity);
                     }
                 }
             }
@@ -1,9 +1,10 @@
 use specs::prelude::*;
 
-use crate::RunState;
-
-use super::{Viewshed, Monster, Map, Name, Position, WantsToMelee};
-use rltk::{Point, console};
+use super::{
+    Viewshed, Monster, Map, Position, WantsToMelee, Confusion, MyTurn,
+    Door, BlocksTile, BlocksVisibility, Renderable, EntityMoved, set_door_open
+};
+use rltk::Point;
 
 pub struct MonsterAI {}
 
@@ -13,12 +14,18 @@ impl<'a> System<'a> for MonsterAI {
         WriteExpect<'a, Map>,
         ReadExpect<'a, Point>,
         ReadExpect<'a, Entity>,
-        ReadExpect<'a, RunState>,
         Entities<'a>,
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Monster>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, MyTurn>,
+        WriteStorage<'a, Door>,
+        WriteStorage<'a, BlocksTile>,
+        WriteStorage<'a, BlocksVisibility>,
+        WriteStorage<'a, Renderable>,
+        WriteStorage<'a, EntityMoved>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -26,17 +33,39 @@ impl<'a> System<'a> for MonsterAI {
             mut map, 
             player_pos, 
             player_entity,
-            runstate,
             entities,
             mut viewshed, 
             monster, 
             mut monster_pos,
-            mut wants_to_melee
+            mut wants_to_melee,
+            mut confused,
+            turns,
+            mut doors,
+            mut blockers,
+            mut sight_blockers,
+            mut renderables,
+            mut moved
         ) = data;
 
-        for (entity, mut viewshed, _monster, monster_pos) in (&entities, &mut viewshed, &monster, &mut monster_pos).join() {
-            if *runstate != RunState::MonsterTurn {
-                return;
+        // Monsters know how to open doors, so shut ones
+        // shouldn't stop them finding a way to the player.
+        let mut shut_doors: Vec<usize> = (&doors, &monster_pos).join()
+            .filter(|(door, _pos)| !door.open)
+            .map(|(_door, pos)| map.xy_idx(pos.x, pos.y))
+            .collect();
+        for idx in shut_doors.iter() {
+            map.blocked[*idx] = false;
+        }
+
+        // Only monsters whose initiative has come up get to act.
+        for (entity, viewshed, _monster, monster_pos, _turn) in (&entities, &mut viewshed, &monster, &mut monster_pos, &turns).join() {
+            // A confused monster loses its turn until the confusion wears off.
+            if let Some(i_am_confused) = confused.get_mut(entity) {
+                i_am_confused.turns -= 1;
+                if i_am_confused.turns < 1 {
+                    confused.remove(entity);
+                }
+                continue;
             }
             
             // In Rust, ReadExpect<'a, T> (from specs) is essentially a smart pointer (it implements Deref<Target = T>). 
@@ -52,22 +81,25 @@ impl<'a> System<'a> for MonsterAI {
                 wants_to_melee.insert(entity, WantsToMelee { target: *player_entity }).expect("Unable to insert attack");
             } else if viewshed.visible_tiles.contains(&*player_pos) {
                 // 1. for each tick we check to see if a player is in the monster's view
-                let distance = rltk::DistanceAlg::Pythagoras.distance2d(
-                    Point::new(monster_pos.x, monster_pos.y), 
-                    *player_pos
-                );
-
                 // 2. if it is we perform an A* Search to find a path to the player
                 let path = rltk::a_star_search(
                     map.xy_idx(monster_pos.x, monster_pos.y) as i32,
                     map.xy_idx(player_pos.x, player_pos.y) as i32,
-                    &mut *map
+                    &*map
                 );
 
-                console::log(path.success);
-
-                // 3. if we are able to find a path
-                if path.success && path.steps.len() > 1 {
+                // 3. if the way is through a shut door, open it instead of moving
+                if path.success && path.steps.len() > 1 && shut_doors.contains(&path.steps[1]) {
+                    let door_idx = path.steps[1];
+                    let door = map.tile_content[door_idx].iter()
+                        .find(|e| doors.get(**e).is_some())
+                        .copied();
+                    if let Some(door) = door {
+                        set_door_open(door, true, &mut doors, &mut blockers, &mut sight_blockers, &mut renderables);
+                    }
+                    shut_doors.retain(|idx| *idx != door_idx);
+                    viewshed.dirty = true;
+                } else if path.success && path.steps.len() > 1 {
                     // Unblock the monster's path.
                     let mut idx = map.xy_idx(monster_pos.x, monster_pos.y);
                     map.blocked[idx] = false;
@@ -79,11 +111,17 @@ impl<'a> System<'a> for MonsterAI {
                     // Update the idx and block the path.
                     idx = map.xy_idx(monster_pos.x, monster_pos.y);
                     map.blocked[idx] = true;
+                    moved.insert(entity, EntityMoved {}).expect("Unable to insert marker");
                     
                     // 5. and update the viewshed's current status (to allow it to check if we are still in range)
                     viewshed.dirty = true;
                 }
             }
         }
+
+        // Any doors still shut go back to blocking the way.
+        for idx in shut_doors.iter() {
+            map.blocked[*idx] = true;
+        }
     }
 }
\ No newline at end of file
@@ -1,38 +1,61 @@
-use rltk::{console, Point, Rltk, VirtualKeyCode};
+use rltk::{Point, VirtualKeyCode};
 use specs::prelude::*;
 
-use super::{Position, Player, Viewshed, State, Map, CombatStats, RunState, WantsToMelee};
-use std::cmp::{min, max};
+use super::{
+    Position, Player, Viewshed, State, Map, CombatStats, Attributes, RunState, WantsToMelee,
+    Item, WantsToPickupItem, GameLog, TileType, Input,
+    Door, BlocksTile, BlocksVisibility, Renderable, EntityMoved, set_door_open
+};
 
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
+    let attributes = ecs.read_storage::<Attributes>();
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut blockers = ecs.write_storage::<BlocksTile>();
+    let mut sight_blockers = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let mut moved = ecs.write_storage::<EntityMoved>();
     let map = ecs.fetch::<Map>(); // Feels odd to couple map to the player like this.
 
     for (entity, _player, pos, viewshed) in (&entities, &mut players, &mut positions, &mut viewsheds).join() {
-        let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
+        let (dest_x, dest_y) = (pos.x + delta_x, pos.y + delta_y);
+        if !map.in_bounds(dest_x, dest_y) {
+            return;
+        }
+        let destination_idx = map.xy_idx(dest_x, dest_y);
 
         for potential_target in map.tile_content[destination_idx].iter() {
-            let target = combat_stats.get(*potential_target);
-                if let Some(_target) = target {
+            // Melee needs both of these, so anything else isn't worth a swing.
+            let target = (combat_stats.get(*potential_target), attributes.get(*potential_target));
+                if let (Some(_stats), Some(_attributes)) = target {
                     // If we have a target lets attempt to melee it
                     wants_to_melee.insert(
                         entity, // Attacker
                         WantsToMelee{ target: *potential_target }
                     ).expect("Add target failed");
-                    console::log(&format!("From Hell's Heart, I stab thee!"));
                     return; // So we do not move after attacking
             }
         }
 
+        // Walking into a shut door opens it, which takes the turn.
+        let shut_door = map.tile_content[destination_idx].iter()
+            .find(|e| doors.get(**e).is_some_and(|door| !door.open))
+            .copied();
+        if let Some(door) = shut_door {
+            set_door_open(door, true, &mut doors, &mut blockers, &mut sight_blockers, &mut renderables);
+            viewshed.dirty = true;
+            return;
+        }
+
         // If we are not on blocked.
         if !map.blocked[destination_idx] {
-            pos.x = min(79, max(0, pos.x + delta_x));
-            pos.y = min(79, max(0, pos.y + delta_y));
+            pos.x = dest_x;
+            pos.y = dest_y;
 
             // Update the point's position.
             // This is a point that is following the player's position.
@@ -40,12 +63,96 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             point_pos.x = pos.x;
             point_pos.y = pos.y;
             viewshed.dirty = true;
+            moved.insert(entity, EntityMoved {}).expect("Unable to insert marker");
+        }
+    }
+}
+
+/// Attempt to pick up an item on the player's tile.
+fn get_item(ecs: &mut World) {
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let items = ecs.read_storage::<Item>();
+    let positions = ecs.read_storage::<Position>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut target_item: Option<Entity> = None;
+    for (item_entity, _item, position) in (&entities, &items, &positions).join() {
+        if position.x == player_pos.x && position.y == player_pos.y {
+            target_item = Some(item_entity);
+        }
+    }
+
+    match target_item {
+        None => gamelog.entries.push("There is nothing here to pick up.".to_string()),
+        Some(item) => {
+            let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+            pickup.insert(
+                *player_entity,
+                WantsToPickupItem { collected_by: *player_entity, item }
+            ).expect("Unable to insert want to pickup");
+        }
+    }
+}
+
+/// Shut every open door next to the player, as long as nothing is
+/// standing or lying in the doorway. Returns true if any door was shut.
+fn try_close_doors(ecs: &mut World) -> bool {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let map = ecs.fetch::<Map>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut blockers = ecs.write_storage::<BlocksTile>();
+    let mut sight_blockers = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut closed_any = false;
+    for dy in -1 ..= 1 {
+        for dx in -1 ..= 1 {
+            let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+            if (dx == 0 && dy == 0) || !map.in_bounds(x, y) {
+                continue;
+            }
+            let content = &map.tile_content[map.xy_idx(x, y)];
+            if content.len() != 1 || !doors.get(content[0]).is_some_and(|door| door.open) {
+                continue;
+            }
+            set_door_open(content[0], false, &mut doors, &mut blockers, &mut sight_blockers, &mut renderables);
+            closed_any = true;
+        }
+    }
+
+    if closed_any {
+        gamelog.entries.push("You close the door.".to_string());
+        if let Some(viewshed) = viewsheds.get_mut(player_entity) {
+            viewshed.dirty = true;
         }
+    } else {
+        gamelog.entries.push("There is no open door next to you.".to_string());
+    }
+    closed_any
+}
+
+/// Returns true if the player is standing on the stairs down,
+/// otherwise lets them know there is nowhere to go.
+fn try_next_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::DownStairs {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog.entries.push("There is no way down from here.".to_string());
+        false
     }
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
+pub fn player_input(gs: &mut State, input: &Input) -> RunState {
+    match input.key {
         None => { 
             return RunState::AwaitingInput
         }
@@ -86,10 +193,37 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             VirtualKeyCode::Numpad1 |
             VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
 
+            // PICK UP
+            VirtualKeyCode::G => get_item(&mut gs.ecs),
+
+            // CLOSE DOORS
+            VirtualKeyCode::C => {
+                if !try_close_doors(&mut gs.ecs) {
+                    return RunState::AwaitingInput;
+                }
+            }
+
+            // DESCEND
+            VirtualKeyCode::Period => {
+                if try_next_level(&mut gs.ecs) {
+                    return RunState::NextLevel;
+                }
+                return RunState::AwaitingInput;
+            }
+
+            // SAVE AND QUIT
+            VirtualKeyCode::Escape => return RunState::SaveGame,
+
+            // INVENTORY
+            VirtualKeyCode::I => return RunState::ShowInventory,
+
+            // DROP
+            VirtualKeyCode::D => return RunState::ShowDropItem,
+
             _ => { 
                 return RunState::AwaitingInput
             }
         },
     }
-    RunState::PlayerTurn
+    RunState::Ticking
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use rltk::RandomNumberGenerator;
+
+/// One possible outcome, and how likely it is compared to the rest.
+pub struct RandomEntry {
+    name: String,
+    weight: i32,
+}
+
+/// Picks names at random, each as likely as its weight.
+/// An entry with a weight of 10 comes up ten times as often as one with 1.
+#[derive(Default)]
+pub struct RandomTable {
+    entries: Vec<RandomEntry>,
+    total_weight: i32,
+}
+
+impl RandomTable {
+    pub fn new() -> RandomTable {
+        RandomTable { entries: Vec::new(), total_weight: 0 }
+    }
+
+    /// Entries with no weight can never come up, so they are left out.
+    pub fn add<S: ToString>(mut self, name: S, weight: i32) -> RandomTable {
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push(RandomEntry { name: name.to_string(), weight });
+        }
+        self
+    }
+
+    /// Nothing can be picked from an empty table.
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<String> {
+        if self.total_weight == 0 {
+            return None;
+        }
+
+        // Walk along the entries until the roll falls inside one.
+        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(entry.name.clone());
+            }
+            roll -= entry.weight;
+        }
+        None
+    }
+}
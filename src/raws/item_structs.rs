@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use super::{RenderableTemplate, check_dice};
+use crate::EquipmentSlot;
+
+/// Anything that can be picked up. An item can be
+/// a consumable, a weapon, something worn, or none of them.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub renderable: RenderableTemplate,
+    pub consumable: Option<ConsumableTemplate>,
+    pub weapon: Option<WeaponTemplate>,
+    pub wearable: Option<WearableTemplate>,
+}
+
+/// Used up when used. Every effect listed is applied.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ConsumableTemplate {
+    pub provides_healing: Option<i32>,
+    /// Aimed at a tile this far away, rather than used on yourself.
+    pub ranged: Option<i32>,
+    pub damage: Option<i32>,
+    pub area_of_effect: Option<i32>,
+    /// Effects that last this many turns.
+    pub confusion: Option<i32>,
+    pub haste: Option<i32>,
+    pub slow: Option<i32>,
+}
+
+/// Held in the melee slot.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeaponTemplate {
+    pub damage: String,
+    pub to_hit: i32,
+}
+
+/// Worn in a slot, adding to armor class.
+#[derive(Deserialize, Clone, Debug)]
+pub struct WearableTemplate {
+    pub slot: EquipmentSlot,
+    pub armor_class: i32,
+}
+
+impl ItemTemplate {
+    pub(super) fn check(&self) -> Result<(), String> {
+        if let Some(weapon) = &self.weapon {
+            check_dice(&weapon.damage)?;
+        }
+        if self.weapon.is_some() && self.wearable.is_some() {
+            return Err("An item can't be both a weapon and worn".to_string());
+        }
+        Ok(())
+    }
+}
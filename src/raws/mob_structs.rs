@@ -0,0 +1,30 @@
+use serde::Deserialize;
+use super::{RenderableTemplate, check_dice};
+
+/// A monster, and the stats it spawns with.
+#[derive(Deserialize, Clone, Debug)]
+pub struct MobTemplate {
+    pub name: String,
+    pub renderable: RenderableTemplate,
+    #[serde(default)]
+    pub blocks_tile: bool,
+    pub vision_range: i32,
+    pub attributes: AttributesTemplate,
+    /// Natural attack, used when the mob has no weapon.
+    pub damage: String,
+}
+
+/// Base values for each attribute, 10 being average.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct AttributesTemplate {
+    pub might: i32,
+    pub fitness: i32,
+    pub quickness: i32,
+    pub intelligence: i32,
+}
+
+impl MobTemplate {
+    pub(super) fn check(&self) -> Result<(), String> {
+        check_dice(&self.damage)
+    }
+}
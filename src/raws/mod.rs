@@ -0,0 +1,172 @@
+use rltk::RGB;
+use serde::Deserialize;
+use super::{Renderable, RandomTable};
+
+mod mob_structs;
+pub use mob_structs::*;
+mod item_structs;
+pub use item_structs::*;
+mod prop_structs;
+pub use prop_structs::*;
+mod spawn_table_structs;
+pub use spawn_table_structs::*;
+mod rawmaster;
+pub use rawmaster::*;
+
+// Everything that can be spawned into a level is described in a data file,
+// so new creatures and items only need an edit to the raws, not a rebuild.
+// The file is read whenever a new world is made, from the working directory.
+
+/// Where the spawn definitions are read from.
+pub const RAWS_PATH: &str = "raws/spawns.json";
+
+/// A copy of the spawn definitions built into the game,
+/// for when it runs somewhere without a raws folder.
+const EMBEDDED_RAWS: &str = include_str!("../../raws/spawns.json");
+
+/// Every template that levels are populated from.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Raws {
+    pub spawn_limits: SpawnLimits,
+    pub mobs: Vec<MobTemplate>,
+    pub items: Vec<ItemTemplate>,
+    #[serde(default)]
+    pub props: Vec<PropTemplate>,
+    pub spawn_table: Vec<SpawnTableEntry>,
+    /// Identifies the text these were read from, so a replay
+    /// can tell whether it has the spawns it was recorded with.
+    #[serde(skip)]
+    pub fingerprint: u64,
+}
+
+/// The most of each kind of thing one room or region can spawn.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct SpawnLimits {
+    pub max_monsters: i32,
+    pub max_items: i32,
+    #[serde(default)]
+    pub max_props: i32,
+}
+
+/// How a template looks on the map. Colours are written as html hex codes.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RenderableTemplate {
+    pub glyph: char,
+    pub fg: String,
+    pub bg: String,
+}
+
+impl RenderableTemplate {
+    pub fn to_renderable(&self, render_order: i32) -> Renderable {
+        Renderable {
+            glyph: rltk::to_cp437(self.glyph),
+            fg: RGB::from_hex(&self.fg).expect("Colours are checked when the raws are loaded"),
+            bg: RGB::from_hex(&self.bg).expect("Colours are checked when the raws are loaded"),
+            render_order,
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        for colour in [&self.fg, &self.bg] {
+            RGB::from_hex(colour).map_err(|_| format!("'{}' is not a colour", colour))?;
+        }
+        Ok(())
+    }
+}
+
+impl Raws {
+    /// Read spawn definitions, and check everything that would
+    /// otherwise only go wrong once something tried to spawn.
+    pub fn parse(text: &str) -> Result<Raws, String> {
+        let mut raws: Raws = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        raws.fingerprint = fingerprint(text);
+
+        let mut names: Vec<&str> = Vec::new();
+        let templates = raws.mobs.iter().map(|m| (&m.name, &m.renderable))
+            .chain(raws.items.iter().map(|i| (&i.name, &i.renderable)))
+            .chain(raws.props.iter().map(|p| (&p.name, &p.renderable)));
+        for (name, renderable) in templates {
+            if names.contains(&name.as_str()) {
+                return Err(format!("'{}' is defined more than once", name));
+            }
+            names.push(name);
+            renderable.check().map_err(|e| format!("{}: {}", name, e))?;
+        }
+
+        for mob in raws.mobs.iter() {
+            mob.check().map_err(|e| format!("{}: {}", mob.name, e))?;
+        }
+        for item in raws.items.iter() {
+            item.check().map_err(|e| format!("{}: {}", item.name, e))?;
+        }
+
+        for entry in raws.spawn_table.iter() {
+            if !names.contains(&entry.name.as_str()) {
+                return Err(format!("The spawn table lists '{}', which isn't defined", entry.name));
+            }
+        }
+
+        // The map builders put these wherever they need them.
+        if !raws.props.iter().any(|p| p.name == "Door") {
+            return Err("There is no 'Door' prop for the map builders to place".to_string());
+        }
+
+        Ok(raws)
+    }
+
+    /// Weighted picks among the mobs that spawn at this depth.
+    pub fn mob_table(&self, depth: i32) -> RandomTable {
+        self.table_for(depth, |name| self.mobs.iter().any(|m| m.name == name))
+    }
+
+    /// Weighted picks among the items that spawn at this depth.
+    pub fn item_table(&self, depth: i32) -> RandomTable {
+        self.table_for(depth, |name| self.items.iter().any(|i| i.name == name))
+    }
+
+    /// Weighted picks among the props that spawn at this depth.
+    pub fn prop_table(&self, depth: i32) -> RandomTable {
+        self.table_for(depth, |name| self.props.iter().any(|p| p.name == name))
+    }
+
+    fn table_for(&self, depth: i32, include: impl Fn(&str) -> bool) -> RandomTable {
+        self.spawn_table.iter()
+            .filter(|entry| include(&entry.name))
+            .fold(RandomTable::new(), |table, entry| table.add(&entry.name, entry.weight_at(depth)))
+    }
+
+    /// The spawn definitions built into the game.
+    pub fn embedded() -> Raws {
+        Raws::parse(EMBEDDED_RAWS).expect("The built in spawn definitions are invalid")
+    }
+}
+
+/// Read the spawn definitions from the raws folder. A missing file quietly
+/// falls back to the built in copy, a broken one says what is wrong first.
+pub fn load_raws() -> Raws {
+    let text = match std::fs::read_to_string(RAWS_PATH) {
+        Ok(text) => text,
+        Err(_) => return Raws::embedded(),
+    };
+
+    match Raws::parse(&text) {
+        Ok(raws) => raws,
+        Err(e) => {
+            rltk::console::log(format!("Unable to load {}, using the built in spawns: {}", RAWS_PATH, e));
+            Raws::embedded()
+        }
+    }
+}
+
+/// FNV-1a, which unlike the standard library's hasher is
+/// guaranteed to give the same answer on every build.
+fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Dice are written the usual way, such as "1d6+2".
+fn check_dice(text: &str) -> Result<(), String> {
+    rltk::parse_dice_string(text).map(|_| ()).map_err(|_| format!("'{}' is not a dice roll", text))
+}
@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use super::RenderableTemplate;
+
+/// Scenery that just sits on the map.
+#[derive(Deserialize, Clone, Debug)]
+pub struct PropTemplate {
+    pub name: String,
+    pub renderable: RenderableTemplate,
+    #[serde(default)]
+    pub blocks_tile: bool,
+    #[serde(default)]
+    pub blocks_visibility: bool,
+    /// Makes the prop a door, starting open or shut. A shut
+    /// door blocks the tile and sight whatever the flags say.
+    pub door_open: Option<bool>,
+    /// Stays out of sight until the player spots it.
+    #[serde(default)]
+    pub hidden: bool,
+    pub entry_trigger: Option<EntryTriggerTemplate>,
+}
+
+/// What happens to anything that steps onto the prop.
+#[derive(Deserialize, Clone, Debug)]
+pub struct EntryTriggerTemplate {
+    pub damage: Option<i32>,
+    #[serde(default)]
+    pub teleport: bool,
+    /// Goes off once, then does nothing.
+    #[serde(default)]
+    pub single_activation: bool,
+}
@@ -0,0 +1,153 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use super::{Raws, MobTemplate, ItemTemplate, PropTemplate};
+use crate::spawner::{attributes, combat_stats, dice};
+use crate::{
+    Position, Name, Viewshed, Monster, BlocksTile, BlocksVisibility, Door, Renderable, set_door_open,
+    Hidden, EntryTrigger, Teleports, SingleActivation,
+    Experience, Initiative, ACTION_COST,
+    Item, Consumable, ProvidesHealing, Ranged, InflictsDamage, AreaOfEffect, Confusion, Haste, Slow,
+    Equippable, EquipmentSlot, MeleeWeapon, DefenseBonus, SerializeMe
+};
+
+// Templates are cloned out of the Raws resource before building,
+// since the world can't be borrowed while an entity is being made in it.
+
+/// Create a monster from its template.
+pub fn spawn_mob(ecs: &mut World, template: &MobTemplate, x: i32, y: i32) -> Entity {
+    // Start everyone at a different point in the turn, so that
+    // a room full of monsters doesn't all move in lockstep.
+    let initiative = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, ACTION_COST);
+    let stats = &template.attributes;
+    let attributes = attributes(stats.might, stats.fitness, stats.quickness, stats.intelligence);
+
+    let mut builder = ecs.create_entity()
+        .with(Position { x, y })
+        .with(template.renderable.to_renderable(1))
+        .with(Viewshed { visible_tiles: Vec::new(), range: template.vision_range, dirty: true })
+        .with(Monster {})
+        .with(Name { name: template.name.clone() })
+        .with(combat_stats(&attributes, &template.damage))
+        .with(attributes)
+        .with(Experience { level: 1, xp: 0 })
+        .with(Initiative { current: initiative });
+    if template.blocks_tile {
+        builder = builder.with(BlocksTile {});
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+/// Create an item lying on the map from its template.
+pub fn spawn_item(ecs: &mut World, template: &ItemTemplate, x: i32, y: i32) -> Entity {
+    let mut builder = ecs.create_entity()
+        .with(Position { x, y })
+        .with(template.renderable.to_renderable(2))
+        .with(Name { name: template.name.clone() })
+        .with(Item {});
+
+    if let Some(consumable) = &template.consumable {
+        builder = builder.with(Consumable {});
+        if let Some(heal_amount) = consumable.provides_healing {
+            builder = builder.with(ProvidesHealing { heal_amount });
+        }
+        if let Some(range) = consumable.ranged {
+            builder = builder.with(Ranged { range });
+        }
+        if let Some(damage) = consumable.damage {
+            builder = builder.with(InflictsDamage { damage });
+        }
+        if let Some(radius) = consumable.area_of_effect {
+            builder = builder.with(AreaOfEffect { radius });
+        }
+        if let Some(turns) = consumable.confusion {
+            builder = builder.with(Confusion { turns });
+        }
+        if let Some(turns) = consumable.haste {
+            builder = builder.with(Haste { turns });
+        }
+        if let Some(turns) = consumable.slow {
+            builder = builder.with(Slow { turns });
+        }
+    }
+
+    if let Some(weapon) = &template.weapon {
+        builder = builder
+            .with(Equippable { slot: EquipmentSlot::Melee })
+            .with(MeleeWeapon { damage: dice(&weapon.damage), to_hit: weapon.to_hit });
+    }
+
+    if let Some(wearable) = &template.wearable {
+        builder = builder
+            .with(Equippable { slot: wearable.slot })
+            .with(DefenseBonus { defense: wearable.armor_class });
+    }
+
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+/// Create a prop from its template.
+pub fn spawn_prop(ecs: &mut World, template: &PropTemplate, x: i32, y: i32) -> Entity {
+    let mut builder = ecs.create_entity()
+        .with(Position { x, y })
+        .with(template.renderable.to_renderable(2))
+        .with(Name { name: template.name.clone() });
+    if template.blocks_tile {
+        builder = builder.with(BlocksTile {});
+    }
+    if template.blocks_visibility {
+        builder = builder.with(BlocksVisibility {});
+    }
+    if template.door_open.is_some() {
+        builder = builder.with(Door { open: true });
+    }
+    if template.hidden {
+        builder = builder.with(Hidden {});
+    }
+    if let Some(trigger) = &template.entry_trigger {
+        builder = builder.with(EntryTrigger {});
+        if let Some(damage) = trigger.damage {
+            builder = builder.with(InflictsDamage { damage });
+        }
+        if trigger.teleport {
+            builder = builder.with(Teleports {});
+        }
+        if trigger.single_activation {
+            builder = builder.with(SingleActivation {});
+        }
+    }
+    let entity = builder.marked::<SimpleMarker<SerializeMe>>().build();
+
+    // Shutting the door sets up everything else that goes with it.
+    if template.door_open == Some(false) {
+        set_door_open(
+            entity,
+            false,
+            &mut ecs.write_storage::<Door>(),
+            &mut ecs.write_storage::<BlocksTile>(),
+            &mut ecs.write_storage::<BlocksVisibility>(),
+            &mut ecs.write_storage::<Renderable>(),
+        );
+    }
+    entity
+}
+
+/// Create whatever the raws call by this name, if anything.
+pub fn spawn_named(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    let (mob, item, prop) = {
+        let raws = ecs.fetch::<Raws>();
+        (
+            raws.mobs.iter().find(|m| m.name == name).cloned(),
+            raws.items.iter().find(|i| i.name == name).cloned(),
+            raws.props.iter().find(|p| p.name == name).cloned(),
+        )
+    };
+
+    if let Some(mob) = mob {
+        return Some(spawn_mob(ecs, &mob, x, y));
+    }
+    if let Some(item) = item {
+        return Some(spawn_item(ecs, &item, x, y));
+    }
+    prop.map(|prop| spawn_prop(ecs, &prop, x, y))
+}
@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+/// How often a template turns up when levels are populated,
+/// and at which depths. Anything not listed never spawns by chance.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpawnTableEntry {
+    pub name: String,
+    pub weight: i32,
+    #[serde(default = "first_level")]
+    pub min_depth: i32,
+    #[serde(default = "no_deepest_level")]
+    pub max_depth: i32,
+    /// Grows more common the deeper the level, by one per level.
+    #[serde(default)]
+    pub add_map_depth_to_weight: bool,
+}
+
+fn first_level() -> i32 {
+    1
+}
+
+fn no_deepest_level() -> i32 {
+    i32::MAX
+}
+
+impl SpawnTableEntry {
+    /// Weight on a level at this depth, 0 outside the depths it spawns at.
+    pub fn weight_at(&self, depth: i32) -> i32 {
+        if depth < self.min_depth || depth > self.max_depth {
+            return 0;
+        }
+        if self.add_map_depth_to_weight {
+            self.weight + depth
+        } else {
+            self.weight
+        }
+    }
+}
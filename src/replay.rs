@@ -0,0 +1,202 @@
+use std::fs::{self, File};
+use std::io::Write;
+use super::input::{Input, key_name, key_from_name};
+
+/// Every new game is recorded here, so a bug report can ship the file.
+pub const RECORDING_PATH: &str = "./last_game.replay";
+
+/// Ticks to wait between inputs when playing back at normal speed.
+const PLAYBACK_DELAY: i32 = 6;
+
+/// Ticks to run per frame when fast-forwarding.
+const FAST_FORWARD_STEPS: i32 = 50;
+
+// A replay file is plain text. The header holds the seed, a fingerprint of
+// the raws the game was played with and, if one was forced with --map, the
+// map builder. Then each input the game acted on gets a line of its own:
+// the key's name, "click x y", or both, e.g.
+//
+//   seed 12345
+//   raws 6d1c0e3ba4f2a9b7
+//   map bsp
+//   Up
+//   I
+//   A
+//   click 31 20
+
+/// Writes each input to the replay file as it happens,
+/// so the file is complete even if the game crashes.
+pub struct Recorder {
+    file: File,
+    /// A save was made during this recording, so loading it
+    /// can be replayed too. Loading any other save can't be.
+    pub saved: bool,
+}
+
+impl Recorder {
+    pub fn start(path: &str, seed: u64, raws_fingerprint: u64, map_builder_name: Option<&str>) -> std::io::Result<Recorder> {
+        let mut file = File::create(path)?;
+        writeln!(file, "seed {}", seed)?;
+        writeln!(file, "raws {:016x}", raws_fingerprint)?;
+        if let Some(name) = map_builder_name {
+            writeln!(file, "map {}", name)?;
+        }
+        Ok(Recorder { file, saved: false })
+    }
+
+    pub fn record(&mut self, input: &Input) {
+        // Losing the recording isn't worth ending the game over.
+        if writeln!(self.file, "{}", format_input(input)).is_err() {
+            rltk::console::log("Unable to write to the replay file");
+        }
+    }
+}
+
+/// A recorded game being fed back through the game,
+/// along with the playback controls.
+pub struct Replay {
+    pub seed: u64,
+    /// Left out of hand written replays, which are played with whatever raws there are.
+    pub raws_fingerprint: Option<u64>,
+    pub map_builder_name: Option<String>,
+    inputs: Vec<Input>,
+    next: usize,
+    wait: i32,
+    pub paused: bool,
+    pub fast_forward: bool,
+    step_requested: bool,
+    /// Games saved during a replay are kept here rather than on disk.
+    pub save: Option<String>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read replay '{}': {}", path, e))?;
+        Replay::parse(&text).map_err(|e| format!("Replay '{}': {}", path, e))
+    }
+
+    /// Read a replay from the text of a replay file.
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut seed = None;
+        let mut raws_fingerprint = None;
+        let mut map_builder_name = None;
+        let mut inputs = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => {}
+                Some("seed") => seed = words.next().and_then(|s| s.parse::<u64>().ok()),
+                Some("raws") => {
+                    let fingerprint = words.next().and_then(|s| u64::from_str_radix(s, 16).ok())
+                        .ok_or_else(|| format!("bad raws fingerprint on line {}: {}", number + 1, line))?;
+                    raws_fingerprint = Some(fingerprint);
+                }
+                Some("map") => map_builder_name = words.next().map(|s| s.to_string()),
+                Some(_) => {
+                    let input = parse_input(line)
+                        .ok_or_else(|| format!("bad input on line {}: {}", number + 1, line))?;
+                    inputs.push(input);
+                }
+            }
+        }
+
+        Ok(Replay {
+            seed: seed.ok_or("no seed")?,
+            raws_fingerprint,
+            map_builder_name,
+            inputs,
+            next: 0,
+            wait: PLAYBACK_DELAY,
+            paused: false,
+            fast_forward: false,
+            step_requested: false,
+            save: None,
+        })
+    }
+
+    /// Space pauses, Period steps one input while paused,
+    /// and F toggles fast-forward.
+    pub fn handle_controls(&mut self, live: &Input) {
+        match live.key.and_then(key_name) {
+            Some("Space") => self.paused = !self.paused,
+            Some("Period") => {
+                self.paused = true;
+                self.step_requested = true;
+            }
+            Some("F") => self.fast_forward = !self.fast_forward,
+            _ => {}
+        }
+    }
+
+    pub fn steps_per_frame(&self) -> i32 {
+        if self.fast_forward && !self.paused { FAST_FORWARD_STEPS } else { 1 }
+    }
+
+    /// The next recorded input, if the controls allow one through yet.
+    /// Only call this when the game is waiting on the player.
+    pub fn next_input(&mut self) -> Option<Input> {
+        if self.finished() {
+            return None;
+        }
+        if self.paused {
+            if !self.step_requested {
+                return None;
+            }
+            self.step_requested = false;
+        } else if !self.fast_forward {
+            if self.wait > 0 {
+                self.wait -= 1;
+                return None;
+            }
+            self.wait = PLAYBACK_DELAY;
+        }
+
+        self.next += 1;
+        Some(self.inputs[self.next - 1])
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.inputs.len()
+    }
+
+    /// How many inputs have been played, out of the total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next, self.inputs.len())
+    }
+}
+
+fn format_input(input: &Input) -> String {
+    let mut words = Vec::new();
+    if let Some(name) = input.key.and_then(key_name) {
+        words.push(name.to_string());
+    }
+    if input.left_click {
+        words.push(format!("click {} {}", input.mouse_pos.0, input.mouse_pos.1));
+    }
+    words.join(" ")
+}
+
+fn parse_input(line: &str) -> Option<Input> {
+    let mut input = Input::default();
+    let mut words = line.split_whitespace().peekable();
+    if let Some(word) = words.peek() {
+        if *word != "click" {
+            input.key = Some(key_from_name(word)?);
+            words.next();
+        }
+    }
+    if let Some(word) = words.next() {
+        if word != "click" {
+            return None;
+        }
+        let x = words.next()?.parse::<i32>().ok()?;
+        let y = words.next()?.parse::<i32>().ok()?;
+        input.mouse_pos = (x, y);
+        input.left_click = true;
+    }
+    if words.next().is_some() || input.is_empty() {
+        return None;
+    }
+    Some(input)
+}
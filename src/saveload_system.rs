@@ -0,0 +1,160 @@
+use specs::prelude::*;
+use specs::saveload::{
+    SimpleMarker, SimpleMarkerAllocator, SerializeComponents, DeserializeComponents, MarkedBuilder
+};
+use specs::error::NoError;
+use rltk::RandomNumberGenerator;
+use std::fs;
+use std::path::Path;
+use super::components::*;
+use super::{Map, GameLog, RunStats};
+
+const SAVE_PATH: &str = "./savegame.json";
+
+// Each component storage is written as its own JSON value, one after another.
+// Loading has to read them back in exactly the same order.
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<NoError, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0, // entities
+            &mut $data.1, // marker
+            &mut $data.2, // allocator
+            &mut $de,
+        )
+        .unwrap();
+        )*
+    };
+}
+
+pub fn does_save_exist() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+pub fn delete_save() {
+    if does_save_exist() {
+        fs::remove_file(SAVE_PATH).expect("Unable to delete file");
+    }
+}
+
+/// Write every marked entity, along with the map and game log, to the save file.
+pub fn save_game(ecs: &mut World) {
+    let data = save_to_string(ecs);
+    fs::write(SAVE_PATH, data).expect("Unable to write save file");
+}
+
+/// Replace everything in the world with the contents of the save file.
+pub fn load_game(ecs: &mut World) {
+    let data = fs::read_to_string(SAVE_PATH).expect("Unable to read save file");
+    load_from_str(ecs, &data);
+}
+
+/// Serialize the world the same way save_game does, but keep it in memory.
+pub fn save_to_string(ecs: &mut World) -> String {
+    // Resources can't be serialized, so carry them in a temporary entity.
+    let map_copy = ecs.get_mut::<Map>().unwrap().clone();
+    let log_copy = ecs.get_mut::<GameLog>().unwrap().clone();
+    let stats_copy = ecs.get_mut::<RunStats>().unwrap().clone();
+    // The generator's state is saved too, so a loaded game keeps rolling the same numbers.
+    let rng_copy = ecs.get_mut::<RandomNumberGenerator>().unwrap().clone();
+    let save_helper = ecs
+        .create_entity()
+        .with(SerializationHelper { map: map_copy, log: log_copy, stats: stats_copy, rng: rng_copy })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let mut writer = Vec::new();
+    {
+        let data = (ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>());
+        let mut serializer = serde_json::Serializer::new(&mut writer);
+        serialize_individually!(ecs, serializer, data,
+            Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, BlocksVisibility, Door,
+            Hidden, EntryTrigger, SingleActivation, Spent, Teleports,
+            CombatStats, Attributes, Experience, WantsToMelee, Item, Consumable, ProvidesHealing,
+            Ranged, InflictsDamage, AreaOfEffect, Confusion, Haste, Slow, Initiative, InBackpack, WantsToPickupItem,
+            WantsToUseItem, WantsToDropItem, Equippable, Equipped, MeleeWeapon,
+            DefenseBonus, SerializationHelper
+        );
+    }
+
+    ecs.delete_entity(save_helper).expect("Crash on cleanup");
+    String::from_utf8(writer).expect("Save data is not valid UTF-8")
+}
+
+/// Replace everything in the world with a save made by save_to_string.
+pub fn load_from_str(ecs: &mut World, data: &str) {
+    {
+        let mut to_delete = Vec::new();
+        for entity in ecs.entities().join() {
+            to_delete.push(entity);
+        }
+        for del in to_delete.iter() {
+            ecs.delete_entity(*del).expect("Deletion failed");
+        }
+    }
+
+    let mut de = serde_json::Deserializer::from_str(data);
+
+    {
+        let mut d = (
+            &mut ecs.entities(),
+            &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+            &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>()
+        );
+        deserialize_individually!(ecs, de, d,
+            Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, BlocksVisibility, Door,
+            Hidden, EntryTrigger, SingleActivation, Spent, Teleports,
+            CombatStats, Attributes, Experience, WantsToMelee, Item, Consumable, ProvidesHealing,
+            Ranged, InflictsDamage, AreaOfEffect, Confusion, Haste, Slow, Initiative, InBackpack, WantsToPickupItem,
+            WantsToUseItem, WantsToDropItem, Equippable, Equipped, MeleeWeapon,
+            DefenseBonus, SerializationHelper
+        );
+    }
+
+    // Put the resources back, and point the player resources at the loaded player.
+    let mut delete_me: Option<Entity> = None;
+    {
+        let entities = ecs.entities();
+        let helper = ecs.read_storage::<SerializationHelper>();
+        let player = ecs.read_storage::<Player>();
+        let position = ecs.read_storage::<Position>();
+
+        for (entity, h) in (&entities, &helper).join() {
+            let mut worldmap = ecs.write_resource::<Map>();
+            *worldmap = h.map.clone();
+            worldmap.tile_content = vec![Vec::new(); worldmap.tiles.len()];
+            worldmap.view_blocked = vec![false; worldmap.tiles.len()];
+            let mut gamelog = ecs.write_resource::<GameLog>();
+            *gamelog = h.log.clone();
+            let mut stats = ecs.write_resource::<RunStats>();
+            *stats = h.stats.clone();
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            *rng = h.rng.clone();
+            delete_me = Some(entity);
+        }
+
+        for (entity, _player, pos) in (&entities, &player, &position).join() {
+            let mut player_pos = ecs.write_resource::<rltk::Point>();
+            *player_pos = rltk::Point::new(pos.x, pos.y);
+            let mut player_resource = ecs.write_resource::<Entity>();
+            *player_resource = entity;
+        }
+    }
+
+    ecs.delete_entity(delete_me.unwrap()).expect("Unable to delete helper");
+}
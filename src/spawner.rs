@@ -1,155 +1,177 @@
-use rltk::{RandomNumberGenerator, RGB};
+use rltk::{DiceType, RandomNumberGenerator, RGB};
 use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
 
-use super::{Viewshed, Monster, Name, Position, Renderable, Player, CombatStats, BlocksTile, Rect, MAPWIDTH, Item, Potion};
+use super::{
+    Viewshed, Name, Position, Renderable, Player, CombatStats, Attributes, Attribute, Experience,
+    Initiative, ACTION_COST, max_hit_points, Rect, Map, RandomTable, SerializeMe
+};
+use super::raws::{self, Raws};
 
-const MAX_MONSTERS: i32 = 4;
-const MAX_ITEMS: i32 = 2;
+/// Parse dice written the usual way, such as "1d6+2".
+pub(crate) fn dice(text: &str) -> DiceType {
+    rltk::parse_dice_string(text).unwrap_or_else(|_| panic!("Invalid dice string '{}'", text))
+}
+
+/// Attributes from their base values, in the order might,
+/// fitness, quickness and intelligence.
+pub(crate) fn attributes(might: i32, fitness: i32, quickness: i32, intelligence: i32) -> Attributes {
+    Attributes {
+        might: Attribute::new(might),
+        fitness: Attribute::new(fitness),
+        quickness: Attribute::new(quickness),
+        intelligence: Attribute::new(intelligence),
+    }
+}
+
+/// Full health for a level 1 entity with these attributes.
+pub(crate) fn combat_stats(attributes: &Attributes, damage: &str) -> CombatStats {
+    let max_hp = max_hit_points(attributes, 1);
+    CombatStats { max_hp, hp: max_hp, damage: dice(damage) }
+}
 
 /// Create a Player
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
+    let attributes = attributes(16, 14, 14, 10);
     ecs.create_entity()
         .with(Position { x: player_x, y: player_y })
         .with(Renderable {
             glyph: rltk::to_cp437('@'),
             fg: RGB::named(rltk::YELLOW),
             bg: RGB::named(rltk::BLACK),
+            render_order: 0,
         })
         .with(Player {})
         .with(Name { name: "Player".to_string() })
         .with(Viewshed { visible_tiles: Vec::new(), dirty: true, range: 8 })
-        .with(CombatStats { max_hp: 30, hp: 30, defense: 2, power: 5 })
+        .with(combat_stats(&attributes, "1d4"))
+        .with(attributes)
+        .with(Experience { level: 1, xp: 0 })
+        // A full action away, like anyone who has just acted, so the first
+        // turn isn't handed straight back before anything else can move.
+        .with(Initiative { current: ACTION_COST })
+        .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-pub fn random_monster(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
-
-    // Scope the roll and rng to free the borrow quicker for rng.
-    {
-        let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 2);
-    }
+/// Put a shut door in the doorway at this map index.
+pub fn spawn_door(ecs: &mut World, map: &Map, idx: usize) {
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    raws::spawn_named(ecs, "Door", x, y).expect("The raws are checked for a Door when they load");
+}
 
-    match roll {
-        1 => { orc(ecs, x, y) }
-        _ => { goblin(ecs, x, y) }
+/// Spawn monsters and items somewhere inside a room,
+/// picked by how common they are at the map's depth.
+pub fn spawn_room(ecs: &mut World, map: &Map, room: &Rect) {
+    let mut possible_targets: Vec<usize> = Vec::new();
+    for y in room.y1 + 1 ..= room.y2 {
+        for x in room.x1 + 1 ..= room.x2 {
+            possible_targets.push(map.xy_idx(x, y));
+        }
     }
-}
-// Specific Monsters
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('o'), "Orc");
-}
 
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, rltk::to_cp437('g'), "Goblin");
+    spawn_region(ecs, map, &possible_targets);
 }
 
-/// Create a Monster
-fn monster<S: ToString>(ecs: &mut World, x: i32, y:i32, glyph: rltk::FontCharType, name: S) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true })
-        .with(Monster {})
-        .with(Name { name: name.to_string() })
-        .with(BlocksTile {})
-        .with(CombatStats { max_hp: 16, hp: 16, defense: 1, power: 4 })
-        .build();
-}
-
-pub fn spawn_room(ecs: &mut World, room: &Rect) {
+/// Spawn monsters and items on tiles picked from an arbitrary
+/// set of map indices, for maps that are not made out of rooms.
+pub fn spawn_region(ecs: &mut World, map: &Map, area: &[usize]) {
     let mut monster_spawn_points: Vec<usize> = Vec::new();
     let mut item_spawn_points: Vec<usize> = Vec::new();
-    
+    let mut areas: Vec<usize> = Vec::from(area);
+
+    let (limits, mob_table, item_table, prop_table) = {
+        let raws = ecs.fetch::<Raws>();
+        (raws.spawn_limits, raws.mob_table(map.depth), raws.item_table(map.depth), raws.prop_table(map.depth))
+    };
     let num_monsters = ecs.write_resource::<RandomNumberGenerator>()
-        .roll_dice(1, MAX_MONSTERS + 2) - 3;
+        .roll_dice(1, limits.max_monsters + 2) - 3;
     let num_items = ecs.write_resource::<RandomNumberGenerator>()
-        .roll_dice(1, MAX_ITEMS + 2) - 3;
+        .roll_dice(1, limits.max_items + 2) - 3;
+    // Props are scenery, so a region is as likely to have none as any other number.
+    let num_props = ecs.write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, limits.max_props + 1) - 1;
 
-    // Generate spawn points
+    // Generate spawn points.
+    // Each pick is taken out of the area so that nothing spawns on top of anything else.
     for _i in 0 .. num_monsters {
-        let mut added = false;
-        while !added {
-            /*
-            Notes to myself the original version used a scope to contain the rng borrow.
-            Can achieve the same thing with a short borrow.
-
-            The new version is less efficient. We make two access calls to rng in favor of
-            not using the scope functionality.
-
-            Since I am new to Rust, I actually have no idea what is better.
-
-            I think the rng needs internal state, which is why roll_dice needs mutable access
-            to the RandomNumberGenerator instance. So that it can maintain a random roll between
-            each call (not giving the same number).
-
-            // ORIGINAL VERSION - Long-lived borrow
-            let mut rng = ecs.write_resource::<RandomNumberGenerator>();  // Start borrow
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-            // rng borrow is still active here, preventing other ecs uses
-
-            // FIXED VERSION - Short-lived borrows
-            let x = ecs.write_resource::<RandomNumberGenerator>()  // Start borrow
-                .roll_dice(1, i32::abs(room.x2 - room.x1)) as usize;    // End borrow
-            let y = ecs.write_resource::<RandomNumberGenerator>()  // New borrow
-                .roll_dice(1, i32::abs(room.y2 - room.y1)) as usize;    // End borrow
-             */
-            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-            let idx = (y * MAPWIDTH) + x;
-            if !monster_spawn_points.contains(&idx) {
-                monster_spawn_points.push(idx);
-                added = true;
-            }
+        if areas.is_empty() {
+            break;
         }
+        /*
+        Notes to myself the original version used a scope to contain the rng borrow.
+        Can achieve the same thing with a short borrow.
+
+        The new version is less efficient. We make two access calls to rng in favor of
+        not using the scope functionality.
+
+        Since I am new to Rust, I actually have no idea what is better.
+
+        I think the rng needs internal state, which is why roll_dice needs mutable access
+        to the RandomNumberGenerator instance. So that it can maintain a random roll between
+        each call (not giving the same number).
+
+        // ORIGINAL VERSION - Long-lived borrow
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();  // Start borrow
+        let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
+        let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
+        // rng borrow is still active here, preventing other ecs uses
+
+        // FIXED VERSION - Short-lived borrows
+        let x = ecs.write_resource::<RandomNumberGenerator>()  // Start borrow
+            .roll_dice(1, i32::abs(room.x2 - room.x1)) as usize;    // End borrow
+        let y = ecs.write_resource::<RandomNumberGenerator>()  // New borrow
+            .roll_dice(1, i32::abs(room.y2 - room.y1)) as usize;    // End borrow
+         */
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let array_index = rng.random_slice_index(&areas).unwrap();
+        monster_spawn_points.push(areas.remove(array_index));
+    }
 
     for _i in 0 .. num_items {
-        let mut added = false;
-        while !added {
-            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-            let idx = (y * MAPWIDTH) + x;
-            if !item_spawn_points.contains(&idx) {
-                item_spawn_points.push(idx);
-                added = true;
-            }
+        if areas.is_empty() {
+            break;
         }
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let array_index = rng.random_slice_index(&areas).unwrap();
+        item_spawn_points.push(areas.remove(array_index));
+    }
+
+    let mut prop_spawn_points: Vec<usize> = Vec::new();
+    for _i in 0 .. num_props {
+        if areas.is_empty() {
+            break;
+        }
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let array_index = rng.random_slice_index(&areas).unwrap();
+        prop_spawn_points.push(areas.remove(array_index));
     }
 
     // Actually spawn the monsters
     for idx in monster_spawn_points.iter() {
-        let x = *idx % MAPWIDTH;
-        let y = *idx / MAPWIDTH;
-        random_monster(ecs, x as i32, y as i32);
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        spawn_from_table(ecs, &mob_table, x, y);
     }
 
-    // Actually spawn the potions
+    // Actually spawn the items
     for idx in item_spawn_points.iter() {
-        let x = *idx % MAPWIDTH;
-        let y = *idx / MAPWIDTH;
-        health_potion(ecs, x as i32, y as i32);
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        spawn_from_table(ecs, &item_table, x, y);
+    }
+
+    for idx in prop_spawn_points.iter() {
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        spawn_from_table(ecs, &prop_table, x, y);
     }
-}
 }
 
-pub fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(';'), 
-            fg: RGB::named(rltk::MAGENTA), 
-            bg: RGB::named(rltk::BLACK) 
-        })
-        .with(Name { name: "Health Potion".to_string() })
-        .with(Item {})
-        .with(Potion { heal_amount: 8 })
-        .build();
-}
\ No newline at end of file
+/// Spawn whatever comes up on the table, if anything does.
+fn spawn_from_table(ecs: &mut World, table: &RandomTable, x: i32, y: i32) {
+    let roll = table.roll(&mut ecs.write_resource::<RandomNumberGenerator>());
+    if let Some(name) = roll {
+        raws::spawn_named(ecs, &name, x, y);
+    }
+}
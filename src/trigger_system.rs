@@ -0,0 +1,119 @@
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+use super::{
+    Map, TileType, Position, Viewshed, Name, GameLog, EntityMoved, EntryTrigger, Hidden,
+    SingleActivation, Spent, Teleports, InflictsDamage, SufferDamage
+};
+
+/// Sets off the traps under anything that moved this turn.
+pub struct TriggerSystem {}
+
+impl<'a> System<'a> for TriggerSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, Point>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, EntryTrigger>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, SingleActivation>,
+        WriteStorage<'a, Spent>,
+        ReadStorage<'a, Teleports>,
+        ReadStorage<'a, InflictsDamage>,
+        WriteStorage<'a, SufferDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            mut player_pos,
+            player_entity,
+            mut rng,
+            mut log,
+            entities,
+            mut moved,
+            mut positions,
+            mut viewsheds,
+            names,
+            triggers,
+            mut hidden,
+            single_activations,
+            mut spent,
+            teleports,
+            inflicts_damage,
+            mut suffer_damage
+        ) = data;
+
+        // Traps that have gone off for good, and who is being sent elsewhere.
+        // Both wait until the loop is done with the storages they change.
+        let mut sprung: Vec<Entity> = Vec::new();
+        let mut teleported: Vec<Entity> = Vec::new();
+
+        for (entity, _moved, pos) in (&entities, &moved, &positions).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            for trap in map.tile_content[idx].iter() {
+                if *trap == entity || triggers.get(*trap).is_none() || spent.get(*trap).is_some() || sprung.contains(trap) {
+                    continue;
+                }
+
+                // Nobody can miss a trap that has just gone off.
+                hidden.remove(*trap);
+                if map.visible_tiles[idx] {
+                    let victim_name = names.get(entity).map_or("Something", |n| n.name.as_str());
+                    let trap_name = names.get(*trap).map_or("a trap", |n| n.name.as_str());
+                    log.entries.push(format!("{} sets off the {}!", victim_name, trap_name));
+                }
+
+                if let Some(damage) = inflicts_damage.get(*trap) {
+                    SufferDamage::new_damage(&mut suffer_damage, entity, damage.damage, *trap);
+                }
+                if teleports.get(*trap).is_some() && !teleported.contains(&entity) {
+                    teleported.push(entity);
+                }
+                if single_activations.get(*trap).is_some() {
+                    sprung.push(*trap);
+                }
+            }
+        }
+        moved.clear();
+
+        // Spent traps keep their trigger, so they are still known to be traps.
+        for trap in sprung {
+            spent.insert(trap, Spent {}).expect("Unable to insert marker");
+        }
+
+        // Anywhere will do as long as there is room to stand there.
+        let open_tiles: Vec<usize> = if teleported.is_empty() {
+            Vec::new()
+        } else {
+            (0..map.tiles.len())
+                .filter(|idx| map.tiles[*idx] == TileType::Floor && !map.blocked[*idx])
+                .collect()
+        };
+        for entity in teleported {
+            if open_tiles.is_empty() {
+                break;
+            }
+            let destination = open_tiles[rng.roll_dice(1, open_tiles.len() as i32) as usize - 1];
+            let (x, y) = (destination as i32 % map.width, destination as i32 / map.width);
+            if let Some(pos) = positions.get_mut(entity) {
+                pos.x = x;
+                pos.y = y;
+            }
+            if let Some(viewshed) = viewsheds.get_mut(entity) {
+                viewshed.dirty = true;
+            }
+            if entity == *player_entity {
+                *player_pos = Point::new(x, y);
+                log.entries.push("You are somewhere else!".to_string());
+            }
+        }
+    }
+}
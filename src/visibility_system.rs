@@ -1,6 +1,12 @@
 use specs::prelude::*;
-use super::{Viewshed, Position, Map, Player};
-use rltk::{field_of_view, Point};
+use super::{Viewshed, Position, Map, Player, Hidden, Name, Attributes, GameLog};
+use rltk::{field_of_view, Point, RandomNumberGenerator};
+
+/// How far away, in tiles, the player can notice hidden things.
+const PERCEPTION_RANGE: f32 = 3.0;
+
+/// What a d20 plus intelligence bonus has to reach to spot something hidden.
+const PERCEPTION_DC: i32 = 15;
 
 pub struct VisibilitySystem {}
 
@@ -10,11 +16,16 @@ impl<'a> System<'a> for VisibilitySystem {
         Entities<'a>,
         WriteStorage<'a, Viewshed>, 
         WriteStorage<'a, Position>,
-        ReadStorage<'a, Player>
+        ReadStorage<'a, Player>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Attributes>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, mut rng, mut log, mut hidden, names, attributes) = data;
 
         for (entity, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
@@ -58,6 +69,26 @@ impl<'a> System<'a> for VisibilitySystem {
                         map.revealed_tiles[idx] = true;
                         map.visible_tiles[idx] = true;
                     }
+
+                    // Every time the player looks around, they get a
+                    // chance to notice anything hidden close by.
+                    let perception = attributes.get(entity).map_or(0, |a| a.intelligence.bonus);
+                    let here = Point::new(pos.x, pos.y);
+                    for vis in viewshed.visible_tiles.iter() {
+                        if rltk::DistanceAlg::Pythagoras.distance2d(here, *vis) > PERCEPTION_RANGE {
+                            continue;
+                        }
+                        let idx = map.xy_idx(vis.x, vis.y);
+                        for other in map.tile_content[idx].iter() {
+                            if hidden.get(*other).is_none() || rng.roll_dice(1, 20) + perception < PERCEPTION_DC {
+                                continue;
+                            }
+                            hidden.remove(*other);
+                            if let Some(name) = names.get(*other) {
+                                log.entries.push(format!("You spot a {}.", name.name));
+                            }
+                        }
+                    }
                 }
             }
         }
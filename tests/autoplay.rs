@@ -0,0 +1,18 @@
+use rust_roguelike::autoplay::{self, Outcome};
+
+#[test]
+fn the_bot_plays_without_panicking() {
+    let report = autoplay::soak(0..10, 1000);
+    for game in report.games.iter() {
+        assert!(!matches!(game.outcome, Outcome::Panicked { .. }), "seed {} panicked: {:?}", game.seed, game.outcome);
+        assert!(game.turns > 0, "seed {} never took a turn", game.seed);
+    }
+}
+
+#[test]
+fn the_same_seed_plays_the_same_game() {
+    let first = autoplay::play_game(7, 1000);
+    let second = autoplay::play_game(7, 1000);
+    assert_eq!(first.outcome, second.outcome);
+    assert_eq!((first.depth, first.turns, first.kills), (second.depth, second.turns, second.kills));
+}
@@ -0,0 +1,900 @@
+use rltk::{Point, RandomNumberGenerator, VirtualKeyCode};
+use specs::prelude::*;
+use rust_roguelike::headless::{Action, HeadlessGame};
+use rust_roguelike::raws::{self, Raws, RAWS_PATH};
+use rust_roguelike::map_builders;
+// One line per area of the game, so new imports have somewhere to go.
+use rust_roguelike::{Map, TileType, Camera, VIEW_WIDTH, VIEW_HEIGHT, RandomTable, Replay};
+use rust_roguelike::{GameLog, RunState, RunStats};
+use rust_roguelike::{Position, Renderable, Name, Viewshed, Monster, BlocksTile, BlocksVisibility, Door};
+use rust_roguelike::{Attribute, Attributes, CombatStats, Experience, Initiative, MyTurn, Haste};
+use rust_roguelike::{InBackpack, Equipped, carry_capacity};
+use rust_roguelike::{Hidden, Spent, known_traps, entities_to_draw};
+
+const PLAYER_START: (i32, i32) = (10, 10);
+
+/// An empty room the size of the map, with the player alone in it.
+fn arena(seed: u64) -> HeadlessGame {
+    let mut game = HeadlessGame::with_map(seed, "simple");
+    let player = game.player_entity();
+    {
+        let ecs = game.ecs_mut();
+        let others: Vec<Entity> = ecs.entities().join().filter(|e| *e != player).collect();
+        ecs.delete_entities(&others).expect("Unable to clear the arena");
+
+        let mut map = ecs.write_resource::<Map>();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let idx = map.xy_idx(x, y);
+                let edge = x == 0 || y == 0 || x == map.width - 1 || y == map.height - 1;
+                map.tiles[idx] = if edge { TileType::Wall } else { TileType::Floor };
+            }
+        }
+    }
+    place_player(&mut game, PLAYER_START.0, PLAYER_START.1);
+    game
+}
+
+fn place_player(game: &mut HeadlessGame, x: i32, y: i32) {
+    let player = game.player_entity();
+    let ecs = game.ecs_mut();
+    *ecs.write_resource::<Point>() = Point::new(x, y);
+    let mut positions = ecs.write_storage::<Position>();
+    let pos = positions.get_mut(player).unwrap();
+    pos.x = x;
+    pos.y = y;
+    ecs.write_storage::<Viewshed>().get_mut(player).unwrap().dirty = true;
+}
+
+fn spawn_orc(game: &mut HeadlessGame, x: i32, y: i32, hp: i32, might: i32, damage: &str) -> Entity {
+    game.ecs_mut()
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('o'),
+            fg: rltk::RGB::named(rltk::RED),
+            bg: rltk::RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Monster {})
+        .with(Name { name: "Orc".to_string() })
+        .with(BlocksTile {})
+        .with(Viewshed { visible_tiles: Vec::new(), dirty: true, range: 8 })
+        .with(CombatStats { max_hp: hp, hp, damage: rltk::parse_dice_string(damage).unwrap() })
+        .with(Attributes {
+            might: Attribute::new(might),
+            fitness: Attribute::new(10),
+            quickness: Attribute::new(10),
+            intelligence: Attribute::new(10),
+        })
+        .with(Initiative { current: 0 })
+        .build()
+}
+
+/// Let the systems see any changes a test has made to the world.
+fn settle(game: &mut HeadlessGame) {
+    game.ecs_mut().maintain();
+    *game.ecs_mut().write_resource::<RunState>() = RunState::PreRun;
+    game.run_until_input();
+}
+
+fn hp(game: &HeadlessGame, entity: Entity) -> i32 {
+    game.ecs().read_storage::<CombatStats>().get(entity).unwrap().hp
+}
+
+#[test]
+fn player_moves_in_every_direction() {
+    let mut game = arena(1);
+    settle(&mut game);
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(11, 10));
+    game.act(Action::Move { dx: 0, dy: 1 });
+    assert_eq!(game.player_position(), Point::new(11, 11));
+    game.act(Action::Move { dx: -1, dy: -1 });
+    assert_eq!(game.player_position(), Point::new(10, 10));
+    game.act(Action::Move { dx: -1, dy: 1 });
+    assert_eq!(game.player_position(), Point::new(9, 11));
+
+    let player = game.player_entity();
+    let positions = game.ecs().read_storage::<Position>();
+    let pos = positions.get(player).unwrap();
+    assert_eq!((pos.x, pos.y), (9, 11));
+}
+
+#[test]
+fn walls_block_movement() {
+    let mut game = arena(2);
+    place_player(&mut game, 1, 1);
+    settle(&mut game);
+
+    game.act(Action::Move { dx: -1, dy: 0 });
+    game.act(Action::Move { dx: 0, dy: -1 });
+    assert_eq!(game.player_position(), Point::new(1, 1));
+}
+
+#[test]
+fn same_seed_and_script_play_out_the_same() {
+    let script = [
+        Action::Move { dx: 1, dy: 0 },
+        Action::Move { dx: 1, dy: 0 },
+        Action::Move { dx: 0, dy: 1 },
+        Action::Move { dx: -1, dy: 0 },
+        Action::Move { dx: 0, dy: -1 },
+        Action::PickUp,
+    ];
+
+    let mut first = HeadlessGame::new(1234);
+    let mut second = HeadlessGame::new(1234);
+    first.run_script(&script);
+    second.run_script(&script);
+
+    assert_eq!(first.player_position(), second.player_position());
+    assert_eq!(first.ecs().fetch::<GameLog>().entries, second.ecs().fetch::<GameLog>().entries);
+    assert!(first.ecs().fetch::<Map>().tiles == second.ecs().fetch::<Map>().tiles);
+}
+
+/// Log entries added since the log held this many.
+fn new_entries(game: &HeadlessGame, since: usize) -> Vec<String> {
+    game.ecs().fetch::<GameLog>().entries[since..].to_vec()
+}
+
+#[test]
+fn bumping_a_monster_attacks_it() {
+    let mut game = arena(3);
+    let orc = spawn_orc(&mut game, 11, 10, 16, 10, "1d4");
+    settle(&mut game);
+
+    let before = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(10, 10));
+    let attacked = new_entries(&game, before).iter().any(|entry| {
+        ["Player hits Orc", "Player critically hits Orc", "Player misses Orc", "Player fumbles the attack on Orc"]
+            .iter().any(|start| entry.starts_with(start))
+    });
+    assert!(attacked);
+
+    for _ in 0..50 {
+        if !game.ecs().is_alive(orc) {
+            break;
+        }
+        game.act(Action::Move { dx: 1, dy: 0 });
+    }
+    assert!(!game.ecs().is_alive(orc));
+    assert_eq!(game.ecs().fetch::<RunStats>().kills, 1);
+
+    // With the orc gone the way is clear.
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(11, 10));
+}
+
+#[test]
+fn bumping_something_without_attributes_is_not_an_attack() {
+    let mut game = arena(24);
+    let dummy = game.ecs_mut()
+        .create_entity()
+        .with(Position { x: 11, y: 10 })
+        .with(Name { name: "Dummy".to_string() })
+        .with(CombatStats { max_hp: 10, hp: 10, damage: rltk::parse_dice_string("1d1").unwrap() })
+        .build();
+    settle(&mut game);
+
+    // Nothing stops the player walking on, so they do, rather than
+    // spending the turn on a swing that can't land.
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(hp(&game, dummy), 10);
+    assert_eq!(game.player_position(), Point::new(11, 10));
+}
+
+#[test]
+fn melee_damage_stays_within_the_dice() {
+    let mut game = arena(5);
+    spawn_orc(&mut game, 11, 10, 10_000, -100, "1d1");
+    settle(&mut game);
+
+    let before = game.ecs().fetch::<GameLog>().entries.len();
+    game.run_script(&[Action::Move { dx: 1, dy: 0 }; 200]);
+    let entries = new_entries(&game, before);
+
+    // The player punches for 1d4 plus a might bonus of 3,
+    // and a critical rolls the dice twice.
+    let damage = |prefix: &str| -> Vec<i32> {
+        entries.iter()
+            .filter_map(|entry| entry.strip_prefix(prefix))
+            .map(|rest| rest.trim_end_matches(" hp").parse::<i32>().unwrap())
+            .collect()
+    };
+    let hits = damage("Player hits Orc, for ");
+    let crits = damage("Player critically hits Orc, for ");
+    assert!(!hits.is_empty() && !crits.is_empty());
+    assert!(hits.iter().all(|d| (4..=7).contains(d)));
+    assert!(crits.iter().all(|d| (5..=11).contains(d)));
+    assert!(entries.iter().any(|entry| entry == "Player fumbles the attack on Orc"));
+
+    // Only a natural 20 lets an orc that far outclassed land a blow.
+    let orc_hits = entries.iter().filter(|entry| entry.starts_with("Orc hits")).count();
+    assert_eq!(orc_hits, 0);
+    assert!(entries.iter().any(|entry| entry.starts_with("Orc critically hits Player")));
+}
+
+#[test]
+fn kills_earn_experience_and_levels() {
+    let mut game = arena(6);
+    let player = game.player_entity();
+    let starting_max_hp = game.ecs().read_storage::<CombatStats>().get(player).unwrap().max_hp;
+
+    // Each level 1 kill is worth 50 xp, and level 2 takes 100.
+    for _ in 0..2 {
+        let orc = spawn_orc(&mut game, 11, 10, 1, 10, "1d1");
+        settle(&mut game);
+        for _ in 0..20 {
+            if !game.ecs().is_alive(orc) {
+                break;
+            }
+            game.act(Action::Move { dx: 1, dy: 0 });
+        }
+        assert!(!game.ecs().is_alive(orc));
+    }
+
+    let experience = game.ecs().read_storage::<Experience>().get(player).unwrap().clone();
+    assert_eq!((experience.level, experience.xp), (2, 100));
+    assert!(game.ecs().fetch::<GameLog>().entries.iter().any(|e| e == "Congratulations, you are now level 2"));
+
+    // Levelling up raises max hp and heals the player to it.
+    let stats = game.ecs().read_storage::<CombatStats>().get(player).unwrap().clone();
+    assert!(stats.max_hp > starting_max_hp);
+    assert_eq!(stats.hp, stats.max_hp);
+}
+
+/// How many times the orc swings at the player while the player takes ten turns.
+fn orc_attacks_in_ten_turns(hasted: bool) -> usize {
+    let mut game = arena(7);
+    let orc = spawn_orc(&mut game, 11, 10, 10_000, 10, "1d1");
+    if hasted {
+        game.ecs_mut().write_storage::<Haste>().insert(orc, Haste { turns: 1000 }).unwrap();
+    }
+    settle(&mut game);
+
+    let before = game.ecs().fetch::<GameLog>().entries.len();
+    game.run_script(&[Action::Move { dx: 1, dy: 0 }; 10]);
+    new_entries(&game, before).iter().filter(|entry| entry.starts_with("Orc ")).count()
+}
+
+#[test]
+fn haste_lets_a_monster_act_more_often() {
+    let normal = orc_attacks_in_ten_turns(false);
+    let hasted = orc_attacks_in_ten_turns(true);
+
+    // The player is a little quicker than an orc, a hasted orc is much quicker.
+    assert!(normal < 10);
+    assert!(hasted >= normal * 2 - 1);
+}
+
+#[test]
+fn dying_ends_the_game() {
+    let mut game = arena(4);
+    spawn_orc(&mut game, 11, 10, 100, 100, "10d10");
+    settle(&mut game);
+
+    // Even this orc can fumble, so give it a few swings.
+    for _ in 0..10 {
+        if game.runstate() == RunState::GameOver {
+            break;
+        }
+        game.act(Action::Move { dx: 1, dy: 0 });
+    }
+
+    assert!(game.runstate() == RunState::GameOver);
+    assert_eq!(game.ecs().fetch::<RunStats>().cause_of_death.as_deref(), Some("Slain by Orc"));
+    // The player is kept around so the game over screen can report on them.
+    assert!(game.ecs().is_alive(game.player_entity()));
+    assert!(hp(&game, game.player_entity()) < 1);
+}
+
+#[test]
+fn everything_in_the_raws_can_be_spawned() {
+    let raws = Raws::parse(&std::fs::read_to_string(RAWS_PATH).unwrap()).expect("The raws should load");
+    let mut game = arena(5);
+
+    let names: Vec<String> = raws.mobs.iter().map(|m| m.name.clone())
+        .chain(raws.items.iter().map(|i| i.name.clone()))
+        .chain(raws.props.iter().map(|p| p.name.clone()))
+        .collect();
+    for name in names.iter() {
+        let entity = raws::spawn_named(game.ecs_mut(), name, 12, 12).expect("Every template should spawn");
+        assert_eq!(game.ecs().read_storage::<Name>().get(entity).unwrap().name, *name);
+    }
+
+    let orc = raws::spawn_named(game.ecs_mut(), "Orc", 13, 13).unwrap();
+    assert!(game.ecs().read_storage::<Monster>().get(orc).is_some());
+    assert!(game.ecs().read_storage::<BlocksTile>().get(orc).is_some());
+    assert_eq!(game.ecs().read_storage::<Viewshed>().get(orc).unwrap().range, 8);
+    assert!(raws::spawn_named(game.ecs_mut(), "Dragon", 13, 13).is_none());
+}
+
+#[test]
+fn broken_raws_are_rejected() {
+    let good = std::fs::read_to_string(RAWS_PATH).unwrap();
+    assert!(Raws::parse(&good).is_ok());
+    assert!(Raws::parse(&good.replace("\"1d6\"", "\"a few\"")).is_err());
+    assert!(Raws::parse(&good.replace("#FF0000", "red")).is_err());
+    assert!(Raws::parse(&good.replace("\"Goblin\"", "\"Orc\"")).is_err());
+    assert!(Raws::parse(&good.replace("\"Door\"", "\"Gate\"")).is_err());
+    assert!(Raws::parse("{}").is_err());
+}
+
+#[test]
+fn spawn_tables_depend_on_depth() {
+    let raws = Raws::parse(&std::fs::read_to_string(RAWS_PATH).unwrap()).unwrap();
+    let mut rng = RandomNumberGenerator::seeded(6);
+    let rolls = |table: RandomTable, rng: &mut RandomNumberGenerator| -> Vec<String> {
+        (0..1000).map(|_| table.roll(rng).unwrap()).collect()
+    };
+
+    let shallow = rolls(raws.item_table(1), &mut rng);
+    assert!(!shallow.iter().any(|name| name == "Fireball Scroll"));
+    assert!(shallow.iter().any(|name| name == "Dagger"));
+
+    let deep = rolls(raws.item_table(10), &mut rng);
+    assert!(deep.iter().any(|name| name == "Fireball Scroll"));
+    assert!(!deep.iter().any(|name| name == "Dagger"));
+
+    // Orcs get more common the deeper you go.
+    let orcs_at = |depth: i32, rng: &mut RandomNumberGenerator| {
+        rolls(raws.mob_table(depth), rng).iter().filter(|name| *name == "Orc").count()
+    };
+    assert!(orcs_at(10, &mut rng) > orcs_at(1, &mut rng) * 3);
+
+    assert_eq!(RandomTable::new().add("Nothing", 0).roll(&mut rng), None);
+}
+
+#[test]
+fn the_camera_follows_the_player_past_the_edge_of_the_screen() {
+    let mut game = arena(7);
+    place_player(&mut game, 75, 30);
+    settle(&mut game);
+
+    for _ in 0..10 {
+        game.act(Action::Move { dx: 1, dy: 0 });
+    }
+    assert_eq!(game.player_position(), Point::new(85, 30));
+
+    // The player stays in the middle of the screen wherever they are on the map.
+    let camera = Camera::new(game.ecs());
+    let middle = Point::new(VIEW_WIDTH / 2, VIEW_HEIGHT / 2);
+    assert_eq!(camera.world_to_screen(Point::new(85, 30)), Some(middle));
+    assert_eq!(camera.screen_to_world(middle), Some(Point::new(85, 30)));
+    assert_eq!(camera.world_to_screen(Point::new(0, 30)), None);
+    assert_eq!(camera.screen_to_world(Point::new(VIEW_WIDTH, 0)), None);
+}
+
+#[test]
+fn clicking_a_target_goes_through_the_camera() {
+    let mut game = arena(8);
+    place_player(&mut game, 85, 30);
+    // Keep the orc from stepping off the target before the scroll goes off.
+    let orc = spawn_orc(&mut game, 88, 30, 100, 10, "1d1");
+    game.ecs_mut().write_storage::<Initiative>().insert(orc, Initiative { current: 1000 }).unwrap();
+    let player = game.player_entity();
+    let scroll = raws::spawn_named(game.ecs_mut(), "Magic Missile Scroll", 0, 0).unwrap();
+    game.ecs_mut().write_storage::<Position>().remove(scroll);
+    game.ecs_mut().write_storage::<InBackpack>().insert(scroll, InBackpack { owner: player }).unwrap();
+    settle(&mut game);
+
+    let target = Camera::new(game.ecs()).world_to_screen(Point::new(88, 30)).unwrap();
+    let since = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::UseItem { slot: 0 });
+    game.act(Action::Click { x: target.x, y: target.y });
+
+    assert_eq!(new_entries(&game, since), vec!["You use Magic Missile Scroll on Orc, inflicting 8 hp."]);
+}
+
+#[test]
+fn every_builder_makes_a_map_of_its_own_size() {
+    let mut sizes = Vec::new();
+    for name in map_builders::BUILDER_NAMES {
+        let game = HeadlessGame::with_map(9, name);
+        let map = game.ecs().fetch::<Map>();
+        assert_eq!(map.tiles.len(), (map.width * map.height) as usize, "{}", name);
+        assert_eq!(map.revealed_tiles.len(), map.tiles.len(), "{}", name);
+
+        let start = game.player_position();
+        assert!(map.in_bounds(start.x, start.y), "{}", name);
+        assert!(map.tiles[map.xy_idx(start.x, start.y)] == TileType::Floor, "{}", name);
+        sizes.push((map.width, map.height));
+    }
+
+    // Caves and dungeons don't have to be the same size.
+    sizes.sort();
+    sizes.dedup();
+    assert!(sizes.len() > 1);
+}
+
+/// Wall off the arena down column x, leaving a shut door at (x, door_y).
+fn wall_with_door(game: &mut HeadlessGame, x: i32, door_y: i32) -> Entity {
+    {
+        let mut map = game.ecs_mut().write_resource::<Map>();
+        for y in 1..map.height - 1 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Wall;
+        }
+        let idx = map.xy_idx(x, door_y);
+        map.tiles[idx] = TileType::Floor;
+    }
+    raws::spawn_named(game.ecs_mut(), "Door", x, door_y).unwrap()
+}
+
+fn door_is_open(game: &HeadlessGame, door: Entity) -> bool {
+    game.ecs().read_storage::<Door>().get(door).unwrap().open
+}
+
+fn player_can_see(game: &HeadlessGame, x: i32, y: i32) -> bool {
+    let viewsheds = game.ecs().read_storage::<Viewshed>();
+    viewsheds.get(game.player_entity()).unwrap().visible_tiles.contains(&Point::new(x, y))
+}
+
+#[test]
+fn doors_block_sight_until_they_are_opened() {
+    let mut game = arena(9);
+    let door = wall_with_door(&mut game, 12, 10);
+    settle(&mut game);
+
+    assert!(!door_is_open(&game, door));
+    assert!(player_can_see(&game, 12, 10));
+    assert!(!player_can_see(&game, 13, 10));
+
+    // The first bump opens the door, the second walks through it.
+    game.act(Action::Move { dx: 1, dy: 0 });
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(11, 10));
+    assert!(door_is_open(&game, door));
+    assert!(game.ecs().read_storage::<BlocksTile>().get(door).is_none());
+    assert!(game.ecs().read_storage::<BlocksVisibility>().get(door).is_none());
+    assert!(player_can_see(&game, 13, 10));
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(13, 10));
+
+    // Shutting it again cuts off the view back the way the player came.
+    game.act(Action::Key(VirtualKeyCode::C));
+    assert!(!door_is_open(&game, door));
+    assert!(!player_can_see(&game, 11, 10));
+    game.act(Action::Move { dx: -1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(13, 10));
+    assert!(door_is_open(&game, door));
+}
+
+/// The glyph that ends up on top at a map position, if anything is drawn there.
+fn glyph_drawn_at(game: &HeadlessGame, x: i32, y: i32) -> Option<char> {
+    entities_to_draw(game.ecs()).iter()
+        .rev()
+        .find(|(pos, _render)| *pos == Point::new(x, y))
+        .map(|(_pos, render)| render.glyph as u8 as char)
+}
+
+#[test]
+fn the_player_is_drawn_over_an_open_door() {
+    let mut game = arena(25);
+    wall_with_door(&mut game, 12, 10);
+    settle(&mut game);
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(glyph_drawn_at(&game, 12, 10), Some('/'));
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(12, 10));
+    assert_eq!(glyph_drawn_at(&game, 12, 10), Some('@'));
+}
+
+#[test]
+fn monsters_open_doors_to_get_at_the_player() {
+    let mut game = arena(10);
+    let door = wall_with_door(&mut game, 12, 10);
+    let orc = spawn_orc(&mut game, 13, 10, 10, 10, "1d4");
+    settle(&mut game);
+
+    // The door hides the player, so pretend the orc heard them.
+    {
+        let mut viewsheds = game.ecs_mut().write_storage::<Viewshed>();
+        let viewshed = viewsheds.get_mut(orc).unwrap();
+        viewshed.visible_tiles = vec![Point::new(9, 10), Point::new(10, 10)];
+        viewshed.dirty = false;
+    }
+    game.act(Action::Move { dx: -1, dy: 0 });
+
+    assert!(door_is_open(&game, door));
+    let positions = game.ecs().read_storage::<Position>();
+    let orc_pos = positions.get(orc).unwrap();
+    assert_eq!((orc_pos.x, orc_pos.y), (13, 10));
+}
+
+/// Set how good the player is at noticing hidden things.
+fn set_perception(game: &mut HeadlessGame, bonus: i32) {
+    let player = game.player_entity();
+    let mut attributes = game.ecs_mut().write_storage::<Attributes>();
+    attributes.get_mut(player).unwrap().intelligence.bonus = bonus;
+}
+
+fn is_hidden(game: &HeadlessGame, entity: Entity) -> bool {
+    game.ecs().read_storage::<Hidden>().get(entity).is_some()
+}
+
+#[test]
+fn stepping_on_a_hidden_trap_sets_it_off() {
+    let mut game = arena(11);
+    set_perception(&mut game, -100);
+    let trap = raws::spawn_named(game.ecs_mut(), "Bear Trap", 11, 10).unwrap();
+    settle(&mut game);
+    let player = game.player_entity();
+    let start_hp = hp(&game, player);
+    assert!(is_hidden(&game, trap));
+
+    let log_len = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(hp(&game, player), start_hp - 6);
+    assert!(!is_hidden(&game, trap));
+    assert!(new_entries(&game, log_len).contains(&"Player sets off the Bear Trap!".to_string()));
+
+    // A bear trap only goes off once.
+    game.act(Action::Move { dx: -1, dy: 0 });
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(11, 10));
+    assert_eq!(hp(&game, player), start_hp - 6);
+    assert!(game.ecs().read_storage::<Spent>().get(trap).is_some());
+}
+
+#[test]
+fn sprung_traps_stay_on_the_map_out_of_sight() {
+    let mut game = arena(14);
+    set_perception(&mut game, -100);
+    let trap = raws::spawn_named(game.ecs_mut(), "Bear Trap", 11, 10).unwrap();
+    settle(&mut game);
+    assert!(known_traps(game.ecs()).is_empty());
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    for _ in 0..10 {
+        game.act(Action::Move { dx: 1, dy: 0 });
+    }
+    assert!(!player_can_see(&game, 11, 10));
+    assert!(!is_hidden(&game, trap));
+    assert_eq!(known_traps(game.ecs()), vec![trap]);
+}
+
+#[test]
+fn whoever_stands_on_a_trap_is_drawn_over_it() {
+    let mut game = arena(26);
+    set_perception(&mut game, -100);
+    let trap = raws::spawn_named(game.ecs_mut(), "Spike Pit", 11, 10).unwrap();
+    game.ecs_mut().write_storage::<Hidden>().remove(trap);
+    spawn_dummy(&mut game, 12, 12);
+    let pit = raws::spawn_named(game.ecs_mut(), "Spike Pit", 12, 12).unwrap();
+    game.ecs_mut().write_storage::<Hidden>().remove(pit);
+    settle(&mut game);
+
+    assert_eq!(glyph_drawn_at(&game, 11, 10), Some('^'));
+    assert_eq!(glyph_drawn_at(&game, 12, 12), Some('o'));
+    game.act(Action::Move { dx: 1, dy: 0 });
+    assert_eq!(game.player_position(), Point::new(11, 10));
+    assert_eq!(glyph_drawn_at(&game, 11, 10), Some('@'));
+}
+
+#[test]
+fn perception_spots_traps_close_by() {
+    let mut game = arena(12);
+    set_perception(&mut game, 100);
+    let near = raws::spawn_named(game.ecs_mut(), "Spike Pit", 12, 10).unwrap();
+    let far = raws::spawn_named(game.ecs_mut(), "Spike Pit", 20, 10).unwrap();
+    settle(&mut game);
+
+    assert!(!is_hidden(&game, near));
+    assert!(is_hidden(&game, far));
+    assert!(game.ecs().fetch::<GameLog>().entries.contains(&"You spot a Spike Pit.".to_string()));
+}
+
+#[test]
+fn teleport_traps_move_the_player() {
+    let mut game = arena(13);
+    set_perception(&mut game, -100);
+    raws::spawn_named(game.ecs_mut(), "Teleport Trap", 11, 10).unwrap();
+    settle(&mut game);
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    let player_pos = game.player_position();
+    assert_ne!(player_pos, Point::new(11, 10));
+    let positions = game.ecs().read_storage::<Position>();
+    let pos = positions.get(game.player_entity()).unwrap();
+    assert_eq!(Point::new(pos.x, pos.y), player_pos);
+}
+
+/// Names of the items the player is carrying or wearing, in order.
+fn player_items(game: &HeadlessGame) -> (Vec<String>, Vec<String>) {
+    let player = game.player_entity();
+    let names = game.ecs().read_storage::<Name>();
+    let backpack = game.ecs().read_storage::<InBackpack>();
+    let equipped = game.ecs().read_storage::<Equipped>();
+    let mut carried: Vec<String> = (&names, &backpack).join()
+        .filter(|(_name, pack)| pack.owner == player)
+        .map(|(name, _pack)| name.name.clone())
+        .collect();
+    let mut worn: Vec<String> = (&names, &equipped).join()
+        .filter(|(_name, equip)| equip.owner == player)
+        .map(|(name, _equip)| name.name.clone())
+        .collect();
+    carried.sort();
+    worn.sort();
+    (carried, worn)
+}
+
+#[test]
+fn saving_and_loading_keeps_the_game() {
+    let mut game = arena(15);
+    raws::spawn_named(game.ecs_mut(), "Dagger", 11, 10).unwrap();
+    raws::spawn_named(game.ecs_mut(), "Health Potion", 12, 10).unwrap();
+    settle(&mut game);
+    game.run_script(&[
+        Action::Move { dx: 1, dy: 0 },
+        Action::PickUp,
+        Action::UseItem { slot: 0 },
+        Action::Move { dx: 1, dy: 0 },
+        Action::PickUp,
+        Action::Move { dx: 0, dy: 1 },
+    ]);
+    let items = player_items(&game);
+    assert_eq!(items, (vec!["Health Potion".to_string()], vec!["Dagger".to_string()]));
+
+    let position = game.player_position();
+    let tiles = game.ecs().fetch::<Map>().tiles.clone();
+    let revealed = game.ecs().fetch::<Map>().revealed_tiles.clone();
+    let log = game.ecs().fetch::<GameLog>().entries.clone();
+
+    let save = game.save();
+    game.load(&save);
+
+    assert_eq!(game.player_position(), position);
+    let positions = game.ecs().read_storage::<Position>();
+    let pos = positions.get(game.player_entity()).unwrap();
+    assert_eq!(Point::new(pos.x, pos.y), position);
+    drop(positions);
+    assert_eq!(player_items(&game), items);
+    assert!(game.ecs().fetch::<Map>().tiles == tiles);
+    assert_eq!(game.ecs().fetch::<Map>().revealed_tiles, revealed);
+    assert_eq!(game.ecs().fetch::<GameLog>().entries, log);
+    assert!(game.runstate() == RunState::AwaitingInput);
+}
+
+#[test]
+fn monsters_do_not_act_twice_after_loading() {
+    let mut game = arena(16);
+    // Only entities from the raws are marked for saving.
+    let orc = raws::spawn_named(game.ecs_mut(), "Orc", 11, 10).unwrap();
+    game.ecs_mut().write_storage::<Initiative>().get_mut(orc).unwrap().current = 1000;
+    settle(&mut game);
+
+    // Save in the middle of a tick the orc has already had its turn in.
+    game.ecs_mut().write_storage::<MyTurn>().insert(orc, MyTurn {}).unwrap();
+    let log_len = game.ecs().fetch::<GameLog>().entries.len();
+    let save = game.save();
+    game.load(&save);
+
+    assert_eq!(game.ecs().read_storage::<Monster>().join().count(), 1);
+    assert_eq!(new_entries(&game, log_len), Vec::<String>::new());
+}
+
+/// Everything a replay has to get right to count as the same game.
+fn end_state(game: &HeadlessGame) -> (Point, i32, i32, Vec<String>) {
+    (
+        game.player_position(),
+        hp(game, game.player_entity()),
+        game.ecs().fetch::<RunStats>().turns,
+        game.ecs().fetch::<GameLog>().entries.clone(),
+    )
+}
+
+#[test]
+fn a_recorded_game_replays_the_same() {
+    let path = std::env::temp_dir().join(format!("headless_{}.replay", std::process::id()));
+    let path = path.to_str().unwrap();
+    let script = [
+        Action::Move { dx: 1, dy: 0 },
+        Action::Move { dx: 1, dy: 1 },
+        Action::Move { dx: 0, dy: 1 },
+        Action::PickUp,
+        Action::Key(VirtualKeyCode::I),
+        Action::Key(VirtualKeyCode::Escape),
+        Action::Move { dx: -1, dy: 0 },
+        Action::Move { dx: 0, dy: -1 },
+    ];
+    let mut game = HeadlessGame::recorded(17, path);
+    game.run_script(&script);
+    let recorded = end_state(&game);
+
+    let replay = Replay::load(path).expect("The recording should parse");
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(replay.seed, 17);
+    assert_eq!(replay.progress(), (0, script.len()));
+
+    let mut replayed = HeadlessGame::replaying(replay).unwrap();
+    replayed.play_replay();
+    assert_eq!(end_state(&replayed), recorded);
+}
+
+#[test]
+fn replays_need_the_raws_they_were_recorded_with() {
+    let path = std::env::temp_dir().join(format!("raws_check_{}.replay", std::process::id()));
+    let path = path.to_str().unwrap();
+    HeadlessGame::recorded(4, path);
+    let text = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let raws = Raws::parse(&std::fs::read_to_string(RAWS_PATH).unwrap()).unwrap();
+    let header = format!("raws {:016x}", raws.fingerprint);
+    assert!(text.lines().any(|line| line == header));
+    assert!(HeadlessGame::replaying(Replay::parse(&text).unwrap()).is_ok());
+
+    let edited = text.replace(&header, &format!("raws {:016x}", raws.fingerprint ^ 1));
+    assert!(HeadlessGame::replaying(Replay::parse(&edited).unwrap()).is_err());
+}
+
+#[test]
+fn malformed_replays_are_rejected() {
+    assert!(Replay::parse("seed 1\nUp\nclick 3 4\nUp click 5 6\n").is_ok());
+    assert!(Replay::parse("").is_err());
+    assert!(Replay::parse("Up\nDown\n").is_err());
+    assert!(Replay::parse("seed lots\nUp\n").is_err());
+    assert!(Replay::parse("seed 1\nNotAKey\n").is_err());
+    assert!(Replay::parse("seed 1\nclick 3\n").is_err());
+    assert!(Replay::parse("seed 1\nclick three four\n").is_err());
+    assert!(Replay::parse("seed 1\nUp Down\n").is_err());
+    assert!(Replay::parse("seed 1\nraws nonsense\n").is_err());
+    assert!(Replay::load("no/such/file.replay").is_err());
+    assert!(HeadlessGame::replaying(Replay::parse("seed 1\nmap nowhere\n").unwrap()).is_err());
+}
+
+/// Put a fresh item from the raws straight into the player's backpack.
+fn give_item(game: &mut HeadlessGame, name: &str) -> Entity {
+    let player = game.player_entity();
+    let item = raws::spawn_named(game.ecs_mut(), name, 0, 0).unwrap();
+    game.ecs_mut().write_storage::<Position>().remove(item);
+    game.ecs_mut().write_storage::<InBackpack>().insert(item, InBackpack { owner: player }).unwrap();
+    item
+}
+
+fn is_carried(game: &HeadlessGame, item: Entity) -> bool {
+    let backpack = game.ecs().read_storage::<InBackpack>();
+    backpack.get(item).is_some_and(|pack| pack.owner == game.player_entity())
+}
+
+fn position_of(game: &HeadlessGame, entity: Entity) -> Option<Point> {
+    game.ecs().read_storage::<Position>().get(entity).map(|pos| Point::new(pos.x, pos.y))
+}
+
+#[test]
+fn items_can_be_picked_up_and_dropped() {
+    let mut game = arena(18);
+    let potion = raws::spawn_named(game.ecs_mut(), "Health Potion", PLAYER_START.0, PLAYER_START.1).unwrap();
+    settle(&mut game);
+
+    let since = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::PickUp);
+    assert!(is_carried(&game, potion));
+    assert_eq!(position_of(&game, potion), None);
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    game.act(Action::DropItem { slot: 0 });
+    assert!(!is_carried(&game, potion));
+    assert_eq!(position_of(&game, potion), Some(Point::new(11, 10)));
+
+    game.act(Action::Move { dx: 1, dy: 0 });
+    game.act(Action::PickUp);
+    assert_eq!(new_entries(&game, since), vec![
+        "You pick up the Health Potion.",
+        "You drop the Health Potion.",
+        "There is nothing here to pick up.",
+    ]);
+}
+
+#[test]
+fn a_full_backpack_refuses_more() {
+    let mut game = arena(19);
+    let capacity = carry_capacity(game.ecs().read_storage::<Attributes>().get(game.player_entity()).unwrap());
+    for _ in 0..capacity {
+        give_item(&mut game, "Bones");
+    }
+    let potion = raws::spawn_named(game.ecs_mut(), "Health Potion", PLAYER_START.0, PLAYER_START.1).unwrap();
+    settle(&mut game);
+
+    let since = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::PickUp);
+    assert!(!is_carried(&game, potion));
+    assert_eq!(position_of(&game, potion), Some(Point::new(PLAYER_START.0, PLAYER_START.1)));
+    assert_eq!(new_entries(&game, since), vec!["You can't carry any more."]);
+}
+
+#[test]
+fn drinking_a_potion_heals_and_uses_it_up() {
+    let mut game = arena(20);
+    let player = game.player_entity();
+    let potion = give_item(&mut game, "Health Potion");
+    game.ecs_mut().write_storage::<CombatStats>().get_mut(player).unwrap().hp = 1;
+    settle(&mut game);
+
+    game.act(Action::UseItem { slot: 0 });
+    assert!(hp(&game, player) > 1);
+    assert!(!game.ecs().is_alive(potion));
+}
+
+/// An orc that won't move or swing at anyone for the rest of the test.
+fn spawn_dummy(game: &mut HeadlessGame, x: i32, y: i32) -> Entity {
+    let orc = spawn_orc(game, x, y, 100, 10, "1d1");
+    game.ecs_mut().write_storage::<Initiative>().get_mut(orc).unwrap().current = 1000;
+    orc
+}
+
+#[test]
+fn ranged_items_aim_at_the_nearest_monster() {
+    let mut game = arena(21);
+    let near = spawn_dummy(&mut game, 13, 10);
+    let far = spawn_dummy(&mut game, 15, 12);
+    give_item(&mut game, "Magic Missile Scroll");
+    settle(&mut game);
+
+    // The cursor starts on the nearest monster, so confirming fires at it.
+    game.act(Action::UseItem { slot: 0 });
+    assert!(matches!(game.runstate(), RunState::ShowTargeting { .. }));
+    game.act(Action::Key(VirtualKeyCode::Return));
+    assert_eq!(hp(&game, near), 92);
+    assert_eq!(hp(&game, far), 100);
+
+    // Escape backs out of aiming and keeps the scroll.
+    let scroll = give_item(&mut game, "Magic Missile Scroll");
+    game.act(Action::UseItem { slot: 0 });
+    game.act(Action::Key(VirtualKeyCode::Escape));
+    assert!(game.runstate() == RunState::AwaitingInput);
+    assert!(is_carried(&game, scroll));
+}
+
+#[test]
+fn area_of_effect_hits_everything_in_the_blast() {
+    let mut game = arena(22);
+    let centre = spawn_dummy(&mut game, 15, 10);
+    let beside = spawn_dummy(&mut game, 16, 11);
+    let outside = spawn_dummy(&mut game, 20, 10);
+    give_item(&mut game, "Fireball Scroll");
+    settle(&mut game);
+
+    let target = Camera::new(game.ecs()).world_to_screen(Point::new(15, 10)).unwrap();
+    game.act(Action::UseItem { slot: 0 });
+    game.act(Action::Click { x: target.x, y: target.y });
+    assert_eq!(hp(&game, centre), 80);
+    assert_eq!(hp(&game, beside), 80);
+    assert_eq!(hp(&game, outside), 100);
+    // The player is well outside the blast.
+    let player = game.player_entity();
+    let max_hp = game.ecs().read_storage::<CombatStats>().get(player).unwrap().max_hp;
+    assert_eq!(hp(&game, player), max_hp);
+}
+
+#[test]
+fn equipping_swaps_out_whatever_is_in_the_slot() {
+    let mut game = arena(23);
+    let player = game.player_entity();
+    let shield = give_item(&mut game, "Shield");
+    settle(&mut game);
+    game.act(Action::UseItem { slot: 0 });
+    let dagger = give_item(&mut game, "Dagger");
+    game.act(Action::UseItem { slot: 0 });
+    let longsword = give_item(&mut game, "Longsword");
+
+    let since = game.ecs().fetch::<GameLog>().entries.len();
+    game.act(Action::UseItem { slot: 0 });
+    assert_eq!(new_entries(&game, since), vec!["You unequip Dagger.", "You equip Longsword."]);
+
+    let equipped = game.ecs().read_storage::<Equipped>();
+    assert!(equipped.get(longsword).is_some_and(|e| e.owner == player));
+    assert!(equipped.get(shield).is_some_and(|e| e.owner == player));
+    assert!(equipped.get(dagger).is_none());
+    drop(equipped);
+    assert!(is_carried(&game, dagger));
+    assert!(!is_carried(&game, longsword));
+}